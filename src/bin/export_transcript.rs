@@ -0,0 +1,223 @@
+//! Exports a game transcript from `message_log.jsonl` without running the bot
+//!
+//! ```text
+//! export_transcript [--config config.yaml] [--log message_log.jsonl] [--state state.yaml]
+//!                   [--format markdown|html]
+//!                   [--channel ID]... [--from UNIX_SECONDS] [--to UNIX_SECONDS]
+//!                   [--reveal-cottages] [--out FILE]
+//! ```
+//!
+//! The game's days, cottage channels and whisper threads are read from the bot's `state.yaml`,
+//! `--from` and `--to` override the time window for games that are no longer in the state file.
+//! Cottages seated earlier in the game are found in the log. Rotated and compressed segments next
+//! to the log are read as well. The log is found the same way the bot finds it, from
+//! the `log` section of `config.yaml`, unless `--log` is given.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::PathBuf,
+    process::ExitCode,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use botc_discord_bot::{
+    message_log::{LogConfig, read_all_segments},
+    transcript::{GameTimeline, TranscriptFormat, TranscriptOptions, build_transcript},
+};
+use poise::serenity_prelude::{ChannelId, UserId};
+use serde::Deserialize;
+
+/// The parts of the bot's state file needed to export a transcript
+#[derive(Deserialize, Default)]
+struct SavedGame {
+    #[serde(default)]
+    players: HashMap<u32, (UserId, ChannelId)>,
+    #[serde(default)]
+    timeline: GameTimeline,
+    #[serde(default)]
+    whispers: SavedWhispers,
+}
+
+#[derive(Deserialize, Default)]
+struct SavedWhispers {
+    #[serde(default)]
+    threads: Vec<SavedWhisper>,
+}
+
+#[derive(Deserialize)]
+struct SavedWhisper {
+    thread_id: ChannelId,
+}
+
+/// The parts of the bot's config file needed to find the log
+#[derive(Deserialize)]
+struct SavedConfig {
+    #[serde(default)]
+    log: LogConfig,
+}
+
+struct Args {
+    config: String,
+    log: Option<String>,
+    state: String,
+    format: TranscriptFormat,
+    channels: HashSet<ChannelId>,
+    from: Option<SystemTime>,
+    to: Option<SystemTime>,
+    reveal_cottages: bool,
+    out: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        config: "config.yaml".to_owned(),
+        log: None,
+        state: "state.yaml".to_owned(),
+        format: TranscriptFormat::Markdown,
+        channels: HashSet::new(),
+        from: None,
+        to: None,
+        reveal_cottages: false,
+        out: None,
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or(format!("Missing value for {arg}"));
+        let time = |value: String| -> Result<SystemTime, String> {
+            let seconds = value
+                .parse()
+                .map_err(|_| format!("Invalid timestamp {value}"))?;
+            Ok(UNIX_EPOCH + Duration::from_secs(seconds))
+        };
+
+        match arg.as_str() {
+            "--config" => args.config = value()?,
+            "--log" => args.log = Some(value()?),
+            "--state" => args.state = value()?,
+            "--format" => {
+                args.format = match value()?.as_str() {
+                    "markdown" | "md" => TranscriptFormat::Markdown,
+                    "html" => TranscriptFormat::Html,
+                    other => return Err(format!("Unknown format {other}")),
+                }
+            }
+            "--channel" => {
+                let value = value()?;
+                args.channels.insert(ChannelId::new(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid channel ID {value}"))?,
+                ));
+            }
+            "--from" => args.from = Some(time(value()?)?),
+            "--to" => args.to = Some(time(value()?)?),
+            "--reveal-cottages" => args.reveal_cottages = true,
+            "--out" => args.out = Some(value()?),
+            other => return Err(format!("Unknown argument {other}")),
+        }
+    }
+
+    Ok(args)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let saved_game: SavedGame = match File::open(&args.state) {
+        Ok(file) => match serde_yml::from_reader(file) {
+            Ok(saved_game) => saved_game,
+            Err(e) => {
+                eprintln!("Could not read {}: {e}", args.state);
+                return ExitCode::FAILURE;
+            }
+        },
+        Err(_) => SavedGame::default(),
+    };
+
+    let mut timeline = saved_game.timeline;
+    if let Some(from) = args.from {
+        timeline.days = vec![from];
+    }
+    if let Some(to) = args.to {
+        timeline.ended = Some(to);
+    }
+    if timeline.days.is_empty() {
+        eprintln!(
+            "No game found in {}, pass --from to export anyway",
+            args.state
+        );
+        return ExitCode::FAILURE;
+    }
+    if args.reveal_cottages && timeline.ended.is_none() {
+        eprintln!(
+            "Cottages can only be revealed once the game is over, pass --to to export anyway"
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let log = match args.log {
+        Some(log) => PathBuf::from(log),
+        None => match File::open(&args.config) {
+            Ok(file) => match serde_yml::from_reader::<_, SavedConfig>(file) {
+                Ok(config) => config.log.path,
+                Err(e) => {
+                    eprintln!("Could not read {}: {e}", args.config);
+                    return ExitCode::FAILURE;
+                }
+            },
+            Err(_) => LogConfig::default().path,
+        },
+    };
+
+    let records = match read_all_segments(&log) {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("Could not open {}: {e}", log.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let records = records.filter_map(|record| match record {
+        Ok(record) => Some(record),
+        Err(e) => {
+            eprintln!("Skipping unreadable line: {e}");
+            None
+        }
+    });
+
+    let transcript = build_transcript(
+        records,
+        TranscriptOptions {
+            timeline,
+            channels: args.channels,
+            cottage_channels: saved_game
+                .players
+                .values()
+                .map(|(_, i)| *i)
+                .chain(saved_game.whispers.threads.iter().map(|i| i.thread_id))
+                .collect(),
+            reveal_cottages: args.reveal_cottages,
+            channel_names: HashMap::new(),
+        },
+    )
+    .render(args.format);
+
+    match args.out {
+        Some(out) => {
+            if let Err(e) = std::fs::write(&out, transcript) {
+                eprintln!("Could not write {out}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{transcript}"),
+    }
+
+    ExitCode::SUCCESS
+}
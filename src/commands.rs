@@ -1,82 +1,123 @@
-use std::{collections::HashMap, time::Duration};
+use std::{fmt::Write, time::Duration};
+
+use botc_discord_bot::{message_log::LogEvent, transcript::TranscriptFormat};
 
 use poise::{
-    CreateReply,
+    ChoiceParameter, CreateReply,
     serenity_prelude::{
-        ChannelId, CreateActionRow, CreateButton, EditMessage, ReactionType, UserId,
+        ButtonStyle, ChannelId, ComponentInteractionCollector, CreateActionRow, CreateAttachment,
+        CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage, GuildId, UserId,
     },
 };
 
 use crate::{
     Context, Error,
+    engine::{self, GameError, GameEvent},
+    game::{CottageTeardown, Game, Nomination, SeatingOrder},
+    gateway::SerenityGateway,
+    modifiers::ReminderToken,
+    rules::LifeEvent,
     state::{
-        CottageNumber, DeadState, FormatMention, PlayerMap, PrintCottages, State, Vote, VoteState,
-        format_vote,
+        Character, CharacterType, CottageNumber, FormatMention, MessageStyle, PrintCottages,
+        Statement, VoteKind, cottages_embed, format_ballot,
     },
 };
 
 async fn is_storyteller(ctx: Context<'_>) -> Result<bool, Error> {
+    let guild_id = guild(ctx)?;
     if ctx
         .author()
-        .has_role(ctx, ctx.guild_id().unwrap(), ctx.data().0.storyteller_role)
+        .has_role(ctx, guild_id, ctx.data().0.storyteller_role)
         .await?
     {
         Ok(true)
     } else {
-        ctx.send(
-            CreateReply::default()
-                .ephemeral(true)
-                .content("You must be a storyteller to use this command"),
-        )
-        .await?;
+        Err(Error::Permission(
+            "You must be a storyteller to use this command".to_owned(),
+        ))
+    }
+}
 
-        Ok(false)
+/// The server the command was used in, commands don't work in DMs
+fn guild(ctx: Context<'_>) -> Result<GuildId, Error> {
+    ctx.guild_id().ok_or_else(|| {
+        Error::Permission("This command can only be used in the game's server".to_owned())
+    })
+}
+
+/// The game on the server the command was used in
+fn game(ctx: Context<'_>) -> Game<'_, SerenityGateway<'_>> {
+    let (config, state, _) = ctx.data();
+    Game {
+        gateway: SerenityGateway {
+            ctx: ctx.serenity_context(),
+            guild: config.guild_id,
+        },
+        config,
+        state,
     }
 }
 
-async fn mutate_active_vote<T>(
+/// Checks the end conditions after a death or execution and asks the storyteller whether to
+/// announce the end of the game
+async fn check_for_game_over(
     ctx: Context<'_>,
-    callback: impl FnOnce(&mut PlayerMap, &mut Vote) -> Result<T, Error>,
-) -> Result<T, Error> {
-    let (_config, state, _) = ctx.data();
-
-    let mut state = state.write().await;
-    let State {
-        players,
-        current_vote,
-        number_of_players,
-        ..
-    } = &mut *state;
-
-    let vote = match current_vote {
-        Some(e) => e,
-        None => {
-            ctx.send(
-                CreateReply::default()
-                    .ephemeral(true)
-                    .content("There is no currently active vote"),
-            )
-            .await?;
-            return Err(Error::Silent);
-        }
+    dead_player: Option<UserId>,
+    event: LifeEvent,
+) -> Result<(), Error> {
+    let Some(game_over) = game(ctx).game_over(dead_player, event).await? else {
+        return Ok(());
     };
 
-    let result = callback(players, vote)?;
-
-    let mut message = ctx
-        .http()
-        .get_message(vote.channel_id, vote.message_id)
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!(
+                    "The game appears to be over: {game_over}\nAnnounce it?"
+                ))
+                .components(vec![CreateActionRow::Buttons(vec![
+                    CreateButton::new("game_over_confirm")
+                        .label("Announce")
+                        .style(ButtonStyle::Success),
+                    CreateButton::new("game_over_dismiss")
+                        .label("Not Yet")
+                        .style(ButtonStyle::Secondary),
+                ])]),
+        )
         .await?;
-    message
-        .edit(
+    let message = reply.message().await?;
+
+    let Some(interaction) = ComponentInteractionCollector::new(ctx)
+        .message_id(message.id)
+        .author_id(ctx.author().id)
+        .timeout(Duration::from_secs(15 * 60))
+        .await
+    else {
+        return Ok(());
+    };
+
+    let confirmed = interaction.data.custom_id == "game_over_confirm";
+    interaction
+        .create_response(
             ctx,
-            EditMessage::new().content(format_vote(players, vote, *number_of_players)),
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(if confirmed {
+                        "Game over announced"
+                    } else {
+                        "The game continues"
+                    })
+                    .components(vec![]),
+            ),
         )
         .await?;
 
-    state.save();
+    if confirmed {
+        game(ctx).end_game(ctx.channel_id(), game_over).await?;
+    }
 
-    Ok(result)
+    Ok(())
 }
 
 #[poise::command(prefix_command, slash_command, check = "is_storyteller")]
@@ -88,7 +129,7 @@ pub async fn set_number_of_players(ctx: Context<'_>, number_of_players: u32) ->
     state
         .players
         .retain(|i, _| u32::from(i.0) <= number_of_players);
-    state.save();
+    state.save()?;
     drop(state);
 
     ctx.reply(format!("Number of players set to {}", number_of_players))
@@ -102,28 +143,50 @@ pub async fn assign_player_to_cottage(
     ctx: Context<'_>,
     cottage_number: u32,
     player_id: UserId,
-    channel_id: ChannelId,
+    #[description = "Leave empty to reuse the player's cottage or have one created"]
+    channel_id: Option<ChannelId>,
+    #[description = "Whether this player is a traveller"] traveller: Option<bool>,
 ) -> Result<(), Error> {
-    let (_config, state, _) = ctx.data();
+    if channel_id.is_none() {
+        // Creating a cottage can take a while
+        ctx.defer().await?;
+    }
+    game(ctx)
+        .seat_player(cottage_number, player_id, channel_id, traveller)
+        .await?;
 
-    let mut state = state.write().await;
-    state
+    post_seating_chart(ctx).await
+}
+
+/// Logs who sits where, so the transcript knows every cottage of the game
+async fn log_seating(ctx: Context<'_>) {
+    let cottages = ctx
+        .data()
+        .1
+        .read()
+        .await
         .players
-        .retain(|_, (user_id, _channel_id)| *user_id != player_id);
-    state.players.insert(
-        CottageNumber::new(cottage_number).unwrap(),
-        (player_id, channel_id),
-    );
-    state.save();
-    let state = state.downgrade();
+        .iter()
+        .map(|(cottage, (user_id, channel_id))| (cottage.0.get(), *user_id, *channel_id))
+        .collect();
+    ctx.data().2.write(LogEvent::Seating { cottages }).await;
+}
+
+async fn post_seating_chart(ctx: Context<'_>) -> Result<(), Error> {
+    log_seating(ctx).await;
+    if ctx.data().0.message_style == MessageStyle::Embed {
+        // Mentions in embeds never ping
+        let embed = cottages_embed(&*ctx.data().1.read().await);
+        ctx.send(CreateReply::default().embed(embed)).await?;
+        return Ok(());
+    }
 
     // We first send a blank message then edit it to avoid pinging every player
-    let message = ctx
-        .reply(format!("**Current Cottage Assignment**:\n",))
-        .await?;
+    let message = ctx.reply("**Current Cottage Assignment**:\n").await?;
 
     tokio::time::sleep(Duration::from_millis(250)).await;
 
+    let state = ctx.data().1.read().await;
     message
         .edit(
             ctx,
@@ -137,14 +200,94 @@ pub async fn assign_player_to_cottage(
     Ok(())
 }
 
+/// Swaps the players in two cottages
 #[poise::command(prefix_command, slash_command, check = "is_storyteller")]
-pub async fn set_defense(ctx: Context<'_>, defense: String) -> Result<(), Error> {
-    mutate_active_vote(ctx, move |_, vote| -> Result<(), Error> {
-        vote.defense = defense;
+pub async fn swap_cottages(ctx: Context<'_>, first: u32, second: u32) -> Result<(), Error> {
+    let mut state = ctx.data().1.write().await;
+    let first = engine::cottage(&state, first)?;
+    let second = engine::cottage(&state, second)?;
+    state.swap_seats(first, second)?;
+    state.save()?;
+    drop(state);
 
-        Ok(())
-    })
-    .await?;
+    post_seating_chart(ctx).await
+}
+
+/// Removes the player from a cottage, leaving it empty
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn vacate_cottage(ctx: Context<'_>, cottage_number: u32) -> Result<(), Error> {
+    let mut state = ctx.data().1.write().await;
+    let cottage = engine::cottage(&state, cottage_number)?;
+    state.check_seats_unlocked()?;
+    state.players.remove(&cottage);
+    state.save()?;
+    drop(state);
+
+    post_seating_chart(ctx).await
+}
+
+/// Randomly reseats every player
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn shuffle_cottages(ctx: Context<'_>) -> Result<(), Error> {
+    let mut state = ctx.data().1.write().await;
+    state.shuffle_seats(&mut rand::rng())?;
+    state.save()?;
+    drop(state);
+
+    post_seating_chart(ctx).await
+}
+
+/// Moves a player to a cottage and shifts everyone in between. New players add a cottage.
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn move_player(
+    ctx: Context<'_>,
+    player_id: UserId,
+    cottage_number: u32,
+    #[description = "Cottage channel for new players, leave empty to have one created"]
+    channel_id: Option<ChannelId>,
+) -> Result<(), Error> {
+    let (_config, state, _) = ctx.data();
+
+    let state_read = state.read().await;
+    // Refuse before a cottage gets created for nothing
+    state_read.check_seats_unlocked()?;
+    let seated = state_read
+        .players
+        .values()
+        .find(|(user_id, _)| *user_id == player_id)
+        .copied();
+    // A new player can also take the seat after the last cottage
+    let last_cottage = state_read.number_of_players + u32::from(seated.is_none());
+    let cottage = CottageNumber::new(cottage_number)
+        .filter(|i| i.0.get() <= last_cottage)
+        .ok_or(GameError::NoSuchCottage {
+            cottage: cottage_number,
+            number_of_players: last_cottage,
+        })?;
+    drop(state_read);
+
+    let player = match (seated, channel_id) {
+        (Some(seated), _) => seated,
+        (None, Some(channel_id)) => (player_id, channel_id),
+        (None, None) => {
+            ctx.defer().await?;
+            (player_id, game(ctx).provision_cottage(player_id).await?)
+        }
+    };
+
+    let mut state = state.write().await;
+    state.move_player(player, cottage)?;
+    state.save()?;
+    drop(state);
+
+    post_seating_chart(ctx).await
+}
+
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn set_defense(ctx: Context<'_>, defense: String) -> Result<(), Error> {
+    game(ctx)
+        .set_statement(Statement::Defense, ctx.author().id, true, defense)
+        .await?;
 
     ctx.send(
         CreateReply::default()
@@ -153,23 +296,19 @@ pub async fn set_defense(ctx: Context<'_>, defense: String) -> Result<(), Error>
     )
     .await?;
 
-    //state.save();
-
     Ok(())
 }
 
 #[poise::command(prefix_command, slash_command, check = "is_storyteller")]
 pub async fn set_accusation(ctx: Context<'_>, accusation: String) -> Result<(), Error> {
-    mutate_active_vote(ctx, move |_, vote| -> Result<(), Error> {
-        vote.accusation = accusation;
-        Ok(())
-    })
-    .await?;
+    game(ctx)
+        .set_statement(Statement::Accusation, ctx.author().id, true, accusation)
+        .await?;
 
     ctx.send(
         CreateReply::default()
             .ephemeral(true)
-            .content("Defense Set"),
+            .content("Accusation Set"),
     )
     .await?;
 
@@ -182,32 +321,17 @@ pub async fn raise_hand(
     player_id: UserId,
     hand_state: bool,
 ) -> Result<(), Error> {
-    let success = mutate_active_vote(ctx, |_, vote| {
-        let entry = vote.vote_state.entry(player_id).or_insert(VoteState::None);
-
-        let success = match entry {
-            VoteState::Yes | VoteState::No => false,
-            e => {
-                *e = if hand_state {
-                    VoteState::HandRaised
-                } else {
-                    VoteState::HandLowered
-                };
-                true
-            }
-        };
-
-        Ok(success)
-    })
-    .await?;
+    game(ctx).set_hand(player_id, hand_state).await?;
 
-    ctx.send(CreateReply::default().ephemeral(true).content(if !success {
-        "Vote has already passed this player"
-    } else if hand_state {
-        "Hand Raised"
-    } else {
-        "Hand Lowered"
-    }))
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content(if hand_state {
+                "Hand Raised"
+            } else {
+                "Hand Lowered"
+            }),
+    )
     .await?;
 
     Ok(())
@@ -215,25 +339,33 @@ pub async fn raise_hand(
 
 #[poise::command(prefix_command, slash_command, check = "is_storyteller")]
 pub async fn vote(ctx: Context<'_>, hand_state: bool) -> Result<(), Error> {
-    mutate_active_vote(ctx, |players, vote| {
-        vote.vote_state.insert(
-            players.get(&vote.clock_hand).ok_or(Error::Silent)?.0,
-            if hand_state {
-                VoteState::Yes
+    game(ctx).cast_vote(hand_state).await?;
+
+    let state = ctx.data().1.read().await;
+    let tally = state
+        .current_vote
+        .as_ref()
+        .map(|vote| {
+            let tally = state.vote_modifiers().tally(vote);
+            if vote.secret {
+                format!(
+                    "{}{tally}",
+                    format_ballot(&state.players, vote, state.number_of_players)
+                )
             } else {
-                VoteState::No
-            },
-        );
-
-        vote.clock_hand = vote.clock_hand.next(players.len() as u32);
+                tally.to_string()
+            }
+        })
+        .unwrap_or_default();
+    drop(state);
 
-        Ok(())
-    })
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content(format!("Voted\n{tally}")),
+    )
     .await?;
 
-    ctx.send(CreateReply::default().ephemeral(true).content("Voted"))
-        .await?;
-
     Ok(())
 }
 
@@ -243,83 +375,454 @@ pub async fn start_vote(
     #[description = "Whoever does the nominatino"] nominator: UserId,
     #[description = "Whoever gets nominated"] nominee: UserId,
     #[description = "eg. \"It will take 5 to tie, 6 to execute\""] description: String,
+    #[description = "Whether this is a call to exile a traveller"] exile: Option<bool>,
+    #[description = "Hide how everyone voted, eg. for Organ Grinder games"] secret: Option<bool>,
+    #[description = "Open a thread for the accusation and defense"] thread: Option<bool>,
 ) -> Result<(), Error> {
-    ctx.defer().await?;
-    let (config, state, _) = ctx.data();
+    ctx.defer_ephemeral().await?;
 
-    let state_read = state.read().await;
+    let kind = if exile.unwrap_or(false) {
+        VoteKind::Exile
+    } else {
+        VoteKind::Execution
+    };
+    let nomination = Nomination {
+        nominator,
+        nominee,
+        description,
+        kind,
+        secret: secret.unwrap_or(false),
+        thread: thread.unwrap_or(false),
+    };
+    game(ctx).start_vote(ctx.channel_id(), nomination).await?;
 
-    let mut dead_status = HashMap::<UserId, DeadState>::new();
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content("Vote started"),
+    )
+    .await?;
 
-    let Some(guild) = ctx.guild().map(|i| i.id.clone()) else {
-        return Err(Error::Silent);
-    };
-    for (user_id, _) in state_read.players.values() {
-        let user = guild.member(ctx, user_id).await?;
-        let is_dead = user.roles.contains(&config.dead_role);
-        let has_dead_vote = user.roles.contains(&config.ghost_vote_available_role);
-
-        dead_status.insert(
-            *user_id,
-            if has_dead_vote {
-                DeadState::DeadVoteAvailable
-            } else if is_dead {
-                DeadState::DeadVoteUsed
-            } else {
-                DeadState::Alive
-            },
-        );
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn start_day(ctx: Context<'_>) -> Result<(), Error> {
+    let events = game(ctx).start_day().await?;
+    let open_whispers = ctx.data().1.read().await.whispers.close_at_night;
+
+    for event in events {
+        match event {
+            GameEvent::NewGame if ctx.data().0.log.rotate_per_game => ctx.data().2.rotate().await,
+            GameEvent::DayStarted { day } => {
+                log_seating(ctx).await;
+                ctx.say(format!("Day {day} has begun")).await?;
+            }
+            _ => (),
+        }
+    }
+
+    if open_whispers {
+        game(ctx).lock_whispers(false).await?;
     }
 
-    let clockhand = match state_read.players.iter().find(|(_, (b, _))| *b == nominee) {
-        Some((cottage_number, _)) => cottage_number.next(state_read.players.len() as u32),
-        None => {
-            ctx.reply("Nominee is not assigned to a cottage!").await?;
-            return Ok(());
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn end_day(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer().await?;
+    let events = game(ctx).end_day().await?;
+    let close_whispers = ctx.data().1.read().await.whispers.close_at_night;
+
+    let mut announcement = vec![];
+    let mut executed = None;
+    for event in events {
+        match event {
+            GameEvent::VoteCutShort { nominee } => announcement.push(format!(
+                "The vote on {} was cut short, only the votes cast so far count",
+                FormatMention(nominee)
+            )),
+            GameEvent::NoExecution => {
+                announcement.push("Nobody reached the threshold, nobody is executed".to_string())
+            }
+            GameEvent::ExecutionTied { nominees, votes } => announcement.push(format!(
+                "{} are tied with {votes} votes, nobody is executed",
+                nominees
+                    .iter()
+                    .map(|i| FormatMention(*i).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" and "),
+            )),
+            GameEvent::Executed { player, votes } => {
+                executed = Some(player);
+                announcement.push(format!(
+                    "{} is executed with {votes} votes",
+                    FormatMention(player)
+                ));
+            }
+            GameEvent::NightFell => announcement.push("\nNight falls".to_string()),
+            _ => (),
         }
+    }
+
+    ctx.say(announcement.join("\n")).await?;
+
+    if close_whispers {
+        game(ctx).lock_whispers(true).await?;
+    }
+
+    check_for_game_over(
+        ctx,
+        executed,
+        match executed {
+            Some(executed) => LifeEvent::Executed(executed),
+            None => LifeEvent::NoExecution,
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn set_character(
+    ctx: Context<'_>,
+    player_id: UserId,
+    #[description = "eg. \"Imp\""] name: String,
+    character_type: CharacterType,
+) -> Result<(), Error> {
+    let (_config, state, _) = ctx.data();
+
+    let mut state = state.write().await;
+    state.characters.insert(
+        player_id,
+        Character {
+            name: name.clone(),
+            character_type,
+        },
+    );
+    state.save()?;
+    drop(state);
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content(format!("{} is the {name}", FormatMention(player_id))),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn kill(ctx: Context<'_>, player_id: UserId) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    game(ctx).mark_dead(player_id).await?;
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content(format!("{} has died", FormatMention(player_id))),
+    )
+    .await?;
+
+    check_for_game_over(ctx, Some(player_id), LifeEvent::Died).await?;
+
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn add_reminder(
+    ctx: Context<'_>,
+    player_id: UserId,
+    token: ReminderToken,
+) -> Result<(), Error> {
+    let (_config, state, _) = ctx.data();
+
+    let mut state = state.write().await;
+    let reminders = state.reminders.entry(player_id).or_default();
+    if !reminders.contains(&token) {
+        reminders.push(token);
+    }
+    state.save()?;
+    drop(state);
+
+    ctx.send(CreateReply::default().ephemeral(true).content(format!(
+        "Added {} to {}",
+        token.name(),
+        FormatMention(player_id)
+    )))
+    .await?;
+
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn remove_reminder(
+    ctx: Context<'_>,
+    player_id: UserId,
+    token: ReminderToken,
+) -> Result<(), Error> {
+    let (_config, state, _) = ctx.data();
+
+    let mut state = state.write().await;
+    if let Some(reminders) = state.reminders.get_mut(&player_id) {
+        reminders.retain(|i| *i != token);
+    }
+    state.save()?;
+    drop(state);
+
+    ctx.send(CreateReply::default().ephemeral(true).content(format!(
+        "Removed {} from {}",
+        token.name(),
+        FormatMention(player_id)
+    )))
+    .await?;
+
+    Ok(())
+}
+
+/// Shows the current vote count with character abilities applied, only to the storyteller
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn tally(ctx: Context<'_>) -> Result<(), Error> {
+    let state = ctx.data().1.read().await;
+    let content = match &state.current_vote {
+        Some(vote) => format!(
+            "{}{}**Threshold:** {}",
+            if vote.secret {
+                format_ballot(&state.players, vote, state.number_of_players)
+            } else {
+                String::new()
+            },
+            state.vote_modifiers().tally(vote),
+            state.vote_modifiers().threshold(vote, &state.players)
+        ),
+        None => "There is no currently active vote".to_string(),
     };
-    drop(state_read);
+    drop(state);
 
-    let reply_handle = ctx
-        .send(
-            CreateReply::default()
-                .reply(true)
-                .content(format!(
-                    "{} has nominated {}",
-                    FormatMention(nominator),
-                    FormatMention(nominee)
-                ))
-                .components(vec![CreateActionRow::Buttons(vec![
-                    CreateButton::new("hand_up_button")
-                        .label("Hand Up")
-                        .emoji(ReactionType::Unicode("🙋".to_string())),
-                    CreateButton::new("hand_down_button")
-                        .label("Hand Down")
-                        .emoji('🙅'),
-                ])]),
-        )
+    ctx.send(CreateReply::default().ephemeral(true).content(content))
         .await?;
-    let message = reply_handle.message().await?;
 
-    let vote = Vote {
-        nominator,
-        nominee,
-        description,
-        accusation: String::new(),
-        defense: String::new(),
-        clock_hand: clockhand,
-        vote_state: HashMap::new(),
-        dead_state: dead_status,
-        message_id: message.id,
-        channel_id: message.channel_id,
-    };
+    Ok(())
+}
+
+/// Reveals the result of a secret ballot, either just the number of votes or the full ballot
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn reveal_votes(
+    ctx: Context<'_>,
+    #[description = "Also show how each player voted"] full_ballot: Option<bool>,
+) -> Result<(), Error> {
+    game(ctx).reveal_votes(full_ballot.unwrap_or(false)).await?;
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content("Votes revealed"),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Exports the current game as a transcript, cottages can be revealed once the game is over
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn export_transcript(
+    ctx: Context<'_>,
+    format: Option<TranscriptFormat>,
+    #[description = "Only include this channel"] channel: Option<ChannelId>,
+    #[description = "Include the cottage channels after the game has ended"]
+    reveal_cottages: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let format = format.unwrap_or(TranscriptFormat::Markdown);
+    let reveal_cottages = reveal_cottages.unwrap_or(false);
+
+    // Make sure we never read a half written line
+    ctx.data().2.flush().await;
+    let transcript = game(ctx)
+        .transcript(format, channel, reveal_cottages)
+        .await?;
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content("Transcript exported")
+            .attachment(CreateAttachment::bytes(
+                transcript,
+                format!("transcript.{}", format.extension()),
+            )),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Opens a private thread with another player that only the two of you and the storytellers see
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn whisper(
+    ctx: Context<'_>,
+    #[description = "Who to whisper to"] player_id: UserId,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let thread_id = game(ctx)
+        .whisper(ctx.channel_id(), ctx.author().id, player_id)
+        .await?;
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content(format!("Whisper away in <#{thread_id}>")),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Lets players choose whether they're pinged in their cottage when it's their turn to vote
+#[poise::command(prefix_command, slash_command)]
+pub async fn clock_hand_pings(
+    ctx: Context<'_>,
+    #[description = "Get pinged with Yes and No buttons when the clock hand reaches you"]
+    enabled: bool,
+) -> Result<(), Error> {
+    let user_id = ctx.author().id;
+    let mut state = ctx.data().1.write().await;
+    if enabled {
+        state.clock_hand_ping_opt_outs.remove(&user_id);
+    } else {
+        state.clock_hand_ping_opt_outs.insert(user_id);
+    }
+    state.save()?;
+    drop(state);
+
+    ctx.send(CreateReply::default().ephemeral(true).content(if enabled {
+        "You'll be pinged when the clock hand reaches you"
+    } else {
+        "You won't be pinged when the clock hand reaches you"
+    }))
+    .await?;
+
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn whisper_settings(
+    ctx: Context<'_>,
+    #[description = "Publicly announce who is whispering to whom"] announce: Option<bool>,
+    #[description = "Lock whisper threads during the night"] close_at_night: Option<bool>,
+) -> Result<(), Error> {
+    let (_config, state, _) = ctx.data();
 
     let mut state = state.write().await;
-    state.current_vote = Some(vote);
-    state.save();
+    if let Some(announce) = announce {
+        state.whispers.announce = announce;
+    }
+    if let Some(close_at_night) = close_at_night {
+        state.whispers.close_at_night = close_at_night;
+    }
+    state.save()?;
+    let summary = format!(
+        "**Whisper Settings**\nAnnounce whispers: {}\nClose at night: {}\n{} messages whispered so far",
+        state.whispers.announce,
+        state.whispers.close_at_night,
+        state
+            .whispers
+            .threads
+            .iter()
+            .map(|i| i.messages)
+            .sum::<u32>()
+    );
     drop(state);
 
-    println!("State dropped, and saved");
+    ctx.send(CreateReply::default().ephemeral(true).content(summary))
+        .await?;
+
+    Ok(())
+}
+
+/// Deletes or archives every cottage channel the bot created, eg. once the game is over
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn close_cottages(ctx: Context<'_>, mode: CottageTeardown) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let (closed, failures) = game(ctx).close_cottages(mode).await?;
+
+    let mut reply = format!(
+        "{} {closed} cottages",
+        match mode {
+            CottageTeardown::Delete => "Deleted",
+            CottageTeardown::Archive => "Archived",
+        }
+    );
+    if !failures.is_empty() {
+        let _ = write!(
+            reply,
+            "\nCouldn't close {}, try again to retry them",
+            failures
+                .iter()
+                .map(|i| format!("<#{i}>"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    ctx.send(CreateReply::default().ephemeral(true).content(reply))
+        .await?;
+
+    Ok(())
+}
+
+/// Posts a message players can use to sign up for the next game
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn open_signups(ctx: Context<'_>) -> Result<(), Error> {
+    game(ctx).open_signups(ctx.channel_id()).await?;
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content("Sign-ups are open"),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Seats the first players in the sign-up queue in the empty cottages and creates their channels
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn seat_signups(ctx: Context<'_>, order: SeatingOrder) -> Result<(), Error> {
+    ctx.defer().await?;
+    game(ctx).seat_signups(order).await?;
+
+    post_seating_chart(ctx).await
+}
+
+/// Replaces a player who dropped out, keeping their seat, character and vote
+#[poise::command(prefix_command, slash_command, check = "is_storyteller")]
+pub async fn substitute(
+    ctx: Context<'_>,
+    #[description = "The player leaving the game"] old: UserId,
+    #[description = "Leave empty to take the first player on the waitlist"] new: Option<UserId>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let (new, cottage) = game(ctx).substitute(old, new).await?;
+
+    ctx.data()
+        .2
+        .write(LogEvent::Substitution {
+            cottage: cottage.0.get(),
+            old,
+            new,
+        })
+        .await;
+
+    ctx.say(format!(
+        "{} takes over cottage {} from {}",
+        FormatMention(new),
+        cottage.0,
+        FormatMention(old)
+    ))
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,106 @@
+//! Deadlines for async games that run over hours, shown as Discord timestamps on the vote
+
+use std::{
+    fmt::Display,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// How long each part of a nomination lasts, no deadline is set for parts left out
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct DeadlineConfig {
+    /// Minutes the nominator and nominee have to make their case
+    pub statement_minutes: Option<u64>,
+    /// Minutes after the nomination until hands are locked
+    pub hand_minutes: Option<u64>,
+    /// Minutes after the nomination until the vote should be done
+    pub vote_minutes: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deadline {
+    Statements,
+    Hands,
+    Vote,
+}
+
+impl Deadline {
+    pub const ALL: [Deadline; 3] = [Deadline::Statements, Deadline::Hands, Deadline::Vote];
+}
+
+/// When each part of a vote is due
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct VoteDeadlines {
+    pub statements: Option<SystemTime>,
+    pub hands: Option<SystemTime>,
+    pub vote: Option<SystemTime>,
+    /// Deadlines the storytellers have already been told about
+    #[serde(default)]
+    pub passed: Vec<Deadline>,
+}
+
+impl VoteDeadlines {
+    /// The deadlines for a vote nominated at `now`
+    pub fn new(config: &DeadlineConfig, now: SystemTime) -> VoteDeadlines {
+        let after = |minutes: Option<u64>| minutes.map(|i| now + Duration::from_secs(i * 60));
+        VoteDeadlines {
+            statements: after(config.statement_minutes),
+            hands: after(config.hand_minutes),
+            vote: after(config.vote_minutes),
+            passed: vec![],
+        }
+    }
+
+    pub fn get(&self, deadline: Deadline) -> Option<SystemTime> {
+        match deadline {
+            Deadline::Statements => self.statements,
+            Deadline::Hands => self.hands,
+            Deadline::Vote => self.vote,
+        }
+    }
+
+    pub fn has_passed(&self, deadline: Deadline) -> bool {
+        self.passed.contains(&deadline)
+    }
+}
+
+/// A relative Discord timestamp, eg. "in 2 hours", in everyone's own timezone
+pub struct FormatTimestamp(pub SystemTime);
+
+impl Display for FormatTimestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seconds = self
+            .0
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        write!(f, "<t:{seconds}:R>")
+    }
+}
+
+/// A line for each deadline the vote has, each starting with a line break so a vote without any
+/// renders nothing
+pub struct FormatDeadlines<'a>(pub &'a VoteDeadlines);
+
+impl Display for FormatDeadlines<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for deadline in Deadline::ALL {
+            let Some(time) = self.0.get(deadline) else {
+                continue;
+            };
+            let passed = self.0.has_passed(deadline);
+            let label = match (deadline, passed) {
+                (Deadline::Statements, false) => "Accusation and defense due",
+                (Deadline::Statements, true) => "Accusation and defense were due",
+                (Deadline::Hands, false) => "Hands lock",
+                (Deadline::Hands, true) => "Hands locked",
+                (Deadline::Vote, false) => "Vote due",
+                (Deadline::Vote, true) => "Vote was due",
+            };
+            write!(f, "\n⏰ {label} {}", FormatTimestamp(time))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+//! The rules of running a day, without any Discord. Commands apply these to the [`State`] and
+//! render the [`GameEvent`]s they return.
+
+use std::{fmt::Display, time::SystemTime};
+
+use botc_discord_bot::transcript::GameTimeline;
+use poise::serenity_prelude::{MessageId, UserId};
+
+use crate::{
+    deadlines::Deadline,
+    modifiers::VoteModifiers,
+    state::{
+        CottageNumber, DeadState, Execution, Phase, PlayerMap, State, Vote, VoteKind, VoteState,
+        determine_execution,
+    },
+};
+
+/// Why an action isn't allowed right now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    NoActiveVote,
+    NotSeated(UserId),
+    NotATraveller(UserId),
+    /// The clock hand already passed this player
+    AlreadyVoted(UserId),
+    /// The clock hand points at a cottage nobody sits in
+    EmptyCottage(CottageNumber),
+    NoSuchCottage {
+        cottage: u32,
+        number_of_players: u32,
+    },
+    /// The statement deadline has passed, only storytellers can change them now
+    StatementsDue,
+    /// The hand deadline has passed
+    HandsLocked,
+    /// The vote deadline has passed, only storytellers can lock in votes now
+    VoteDue,
+    /// A button from an earlier vote was used
+    VoteClosed,
+    /// A player tried to vote while the clock hand points at someone else
+    NotYourTurn(UserId),
+    /// A dead player without a ghost vote voted for an execution
+    NoGhostVote(UserId),
+    /// Reseating would move the clock hand to someone else mid-vote
+    VoteInProgress,
+    /// Only a day can end
+    NotDay,
+}
+
+impl Display for GameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameError::NoActiveVote => write!(f, "There is no currently active vote"),
+            GameError::NotSeated(user_id) => {
+                write!(f, "<@{user_id}> is not assigned to a cottage")
+            }
+            GameError::NotATraveller(_) => write!(f, "Only travellers can be exiled!"),
+            GameError::AlreadyVoted(_) => write!(f, "Vote has already passed this player"),
+            GameError::StatementsDue => {
+                write!(f, "The accusation and defense were due at the deadline")
+            }
+            GameError::HandsLocked => write!(f, "Hands were locked at the deadline"),
+            GameError::VoteDue => write!(f, "The vote was due at the deadline"),
+            GameError::VoteClosed => write!(f, "This vote is already over"),
+            GameError::NotYourTurn(_) => write!(f, "The clock hand isn't on you"),
+            GameError::NoGhostVote(user_id) => {
+                write!(
+                    f,
+                    "<@{user_id}> is dead and has already used their ghost vote"
+                )
+            }
+            GameError::NotDay => write!(f, "It isn't day, there is no day to end"),
+            GameError::VoteInProgress => {
+                write!(f, "Cottages can't change while a vote is in progress")
+            }
+            GameError::EmptyCottage(cottage) => {
+                write!(f, "Nobody is sitting in cottage {}", cottage.0)
+            }
+            GameError::NoSuchCottage {
+                cottage,
+                number_of_players,
+            } => write!(
+                f,
+                "There is no cottage {cottage}, cottages go from 1 to {number_of_players}"
+            ),
+        }
+    }
+}
+
+/// Something that happened in the game, for the Discord side to announce
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    HandChanged {
+        voter: UserId,
+        raised: bool,
+    },
+    Voted {
+        voter: UserId,
+        yes: bool,
+    },
+    /// A dead player spent their one ghost vote
+    GhostVoteUsed {
+        voter: UserId,
+    },
+    ClockHandMoved {
+        cottage: CottageNumber,
+        player: UserId,
+    },
+    /// Every seated player has voted
+    VoteComplete,
+    VoteStarted {
+        nominator: UserId,
+        nominee: UserId,
+        kind: VoteKind,
+    },
+    /// Starting a day after the game ended starts a new game
+    NewGame,
+    DayStarted {
+        day: u32,
+    },
+    Executed {
+        player: UserId,
+        votes: u32,
+    },
+    ExecutionTied {
+        nominees: Vec<UserId>,
+        votes: u32,
+    },
+    NoExecution,
+    /// The day ended before everyone had voted on this nominee, only the votes cast so far count
+    VoteCutShort {
+        nominee: UserId,
+    },
+    NightFell,
+    DeadlinePassed {
+        deadline: Deadline,
+        nominee: UserId,
+    },
+}
+
+/// The first seated cottage after `cottage`, going round the table. Empty cottages are skipped.
+pub fn next_seated(
+    players: &PlayerMap,
+    number_of_players: u32,
+    cottage: CottageNumber,
+) -> Option<CottageNumber> {
+    let mut next = cottage;
+    for _ in 0..number_of_players {
+        next = next.next(number_of_players);
+        if players.contains_key(&next) {
+            return Some(next);
+        }
+    }
+    None
+}
+
+/// Looks up a cottage by number, which has to be within the current number of players
+pub fn cottage(state: &State, number: u32) -> Result<CottageNumber, GameError> {
+    state.cottage(number).ok_or(GameError::NoSuchCottage {
+        cottage: number,
+        number_of_players: state.number_of_players,
+    })
+}
+
+pub fn active_vote(state: &mut State) -> Result<&mut Vote, GameError> {
+    state.current_vote.as_mut().ok_or(GameError::NoActiveVote)
+}
+
+/// Raises or lowers a hand, which is only allowed until the clock hand reaches the player
+pub fn set_hand(
+    state: &mut State,
+    voter: UserId,
+    raised: bool,
+) -> Result<Vec<GameEvent>, GameError> {
+    let vote = active_vote(state)?;
+    if vote.deadlines.has_passed(Deadline::Hands) {
+        return Err(GameError::HandsLocked);
+    }
+    let entry = vote.vote_state.entry(voter).or_insert(VoteState::None);
+    if matches!(entry, VoteState::Yes | VoteState::No) {
+        return Err(GameError::AlreadyVoted(voter));
+    }
+
+    *entry = if raised {
+        VoteState::HandRaised
+    } else {
+        VoteState::HandLowered
+    };
+    Ok(vec![GameEvent::HandChanged { voter, raised }])
+}
+
+/// Marks the deadlines of the active vote that are due by `now`, each one only once. Hands can't
+/// change after the hand deadline.
+pub fn pass_deadlines(state: &mut State, now: SystemTime) -> Vec<GameEvent> {
+    let Some(vote) = &mut state.current_vote else {
+        return vec![];
+    };
+
+    let mut events = vec![];
+    for deadline in Deadline::ALL {
+        if vote.deadlines.get(deadline).is_some_and(|i| i <= now)
+            && !vote.deadlines.has_passed(deadline)
+        {
+            vote.deadlines.passed.push(deadline);
+            events.push(GameEvent::DeadlinePassed {
+                deadline,
+                nominee: vote.nominee,
+            });
+        }
+    }
+    events
+}
+
+/// Lets a player lock in their own vote on the vote posted as `vote_message`, as long as the clock
+/// hand points at them. Once hands are locked the vote has to match their hand, and after the vote
+/// deadline only the storytellers can lock in votes.
+pub fn cast_own_vote(
+    state: &mut State,
+    vote_message: MessageId,
+    voter: UserId,
+    yes: bool,
+) -> Result<Vec<GameEvent>, GameError> {
+    let vote = state.current_vote.as_ref().ok_or(GameError::NoActiveVote)?;
+    if vote.message_id != vote_message {
+        return Err(GameError::VoteClosed);
+    }
+    if state.players.get(&vote.clock_hand).map(|i| i.0) != Some(voter) {
+        return Err(GameError::NotYourTurn(voter));
+    }
+    if vote.deadlines.has_passed(Deadline::Vote) {
+        return Err(GameError::VoteDue);
+    }
+    if vote.deadlines.has_passed(Deadline::Hands)
+        && yes != matches!(vote.vote_state.get(&voter), Some(VoteState::HandRaised))
+    {
+        return Err(GameError::HandsLocked);
+    }
+    if yes && !state.vote_modifiers().voudon_in_play(vote) && !vote.may_vote(voter) {
+        return Err(GameError::NoGhostVote(voter));
+    }
+    cast_vote(state, yes)
+}
+
+/// Locks in the vote of whoever the clock hand points at and moves it on to the next player
+pub fn cast_vote(state: &mut State, yes: bool) -> Result<Vec<GameEvent>, GameError> {
+    let State {
+        players,
+        number_of_players,
+        current_vote,
+        characters,
+        reminders,
+        ..
+    } = state;
+    let modifiers = VoteModifiers {
+        characters,
+        reminders,
+    };
+    let vote = current_vote.as_mut().ok_or(GameError::NoActiveVote)?;
+    let voter = players
+        .get(&vote.clock_hand)
+        .ok_or(GameError::EmptyCottage(vote.clock_hand))?
+        .0;
+
+    let voudon_in_play = modifiers.voudon_in_play(vote);
+    if yes && !voudon_in_play && !vote.may_vote(voter) {
+        return Err(GameError::NoGhostVote(voter));
+    }
+    if modifiers.is_butler(voter)
+        && let Some(master) = modifiers.master()
+    {
+        vote.butler_master_voting = Some(matches!(
+            vote.vote_state.get(&master),
+            Some(VoteState::Yes | VoteState::HandRaised)
+        ));
+    }
+
+    let mut events = vec![GameEvent::Voted { voter, yes }];
+    vote.vote_state
+        .insert(voter, if yes { VoteState::Yes } else { VoteState::No });
+
+    let dead_state = vote.dead_state.entry(voter).or_insert(DeadState::Alive);
+    if yes
+        && vote.kind.uses_ghost_votes()
+        && !voudon_in_play
+        && matches!(dead_state, DeadState::DeadVoteAvailable)
+    {
+        *dead_state = DeadState::DeadVoteUsed;
+        vote.spent_ghost_votes.insert(voter);
+        events.push(GameEvent::GhostVoteUsed { voter });
+    }
+
+    if let Some(next) = next_seated(players, *number_of_players, vote.clock_hand) {
+        vote.clock_hand = next;
+        events.push(GameEvent::ClockHandMoved {
+            cottage: next,
+            player: players[&next].0,
+        });
+    }
+
+    if vote.is_finished(players) {
+        events.push(GameEvent::VoteComplete);
+    }
+
+    Ok(events)
+}
+
+/// Checks a nomination can go ahead, returning where the clock hand starts: the first seated
+/// player after the nominee
+pub fn check_nomination(
+    state: &State,
+    nominee: UserId,
+    kind: VoteKind,
+) -> Result<CottageNumber, GameError> {
+    if kind == VoteKind::Exile && !state.is_traveller(nominee) {
+        return Err(GameError::NotATraveller(nominee));
+    }
+
+    let cottage = state
+        .players
+        .iter()
+        .find(|(_, (user_id, _))| *user_id == nominee)
+        .map(|(cottage, _)| *cottage)
+        .ok_or(GameError::NotSeated(nominee))?;
+
+    Ok(next_seated(&state.players, state.number_of_players, cottage).unwrap_or(cottage))
+}
+
+/// Makes a vote the active one, recording the result of the previous execution vote. Exile votes
+/// are deliberately left out of the history: they don't compete with executions, and the
+/// storyteller decides what happens to the traveller from the votes on the message.
+pub fn open_vote(state: &mut State, vote: Vote) -> Vec<GameEvent> {
+    let event = GameEvent::VoteStarted {
+        nominator: vote.nominator,
+        nominee: vote.nominee,
+        kind: vote.kind,
+    };
+
+    if let Some(previous_vote) = state.current_vote.replace(vote)
+        && previous_vote.kind == VoteKind::Execution
+    {
+        let record = previous_vote.record(&state.players, &state.vote_modifiers());
+        state.vote_history.push(record);
+    }
+
+    vec![event]
+}
+
+pub fn start_day(state: &mut State, now: SystemTime) -> Vec<GameEvent> {
+    let mut events = vec![];
+    if state.phase == Phase::GameOver {
+        state.day = 0;
+        state.timeline = GameTimeline::default();
+        state.whispers.threads.clear();
+        state.display_names.clear();
+        events.push(GameEvent::NewGame);
+    }
+
+    state.day += 1;
+    state.timeline.days.push(now);
+    state.phase = Phase::Day;
+    state.vote_history.clear();
+    state.current_vote = None;
+
+    events.push(GameEvent::DayStarted { day: state.day });
+    events
+}
+
+/// Closes the last vote and executes whoever got the most votes, if anyone
+pub fn end_day(state: &mut State) -> Result<Vec<GameEvent>, GameError> {
+    if state.phase != Phase::Day {
+        return Err(GameError::NotDay);
+    }
+
+    let mut events = vec![];
+    if let Some(vote) = state.current_vote.take() {
+        if !vote.is_finished(&state.players) {
+            events.push(GameEvent::VoteCutShort {
+                nominee: vote.nominee,
+            });
+        }
+        if vote.kind == VoteKind::Execution {
+            let record = vote.record(&state.players, &state.vote_modifiers());
+            state.vote_history.push(record);
+        }
+    }
+
+    events.push(match determine_execution(&state.vote_history) {
+        Execution::Nobody => GameEvent::NoExecution,
+        Execution::Tied(records) => GameEvent::ExecutionTied {
+            nominees: records.iter().map(|i| i.nominee).collect(),
+            votes: records[0].votes,
+        },
+        Execution::Executed(record) => GameEvent::Executed {
+            player: record.nominee,
+            votes: record.votes,
+        },
+    });
+
+    state.vote_history.clear();
+    state.phase = Phase::Night;
+    events.push(GameEvent::NightFell);
+    Ok(events)
+}
@@ -0,0 +1,94 @@
+//! Everything that can go wrong handling a command or interaction, and what to tell the user
+
+use std::fmt::Display;
+
+use poise::{CreateReply, FrameworkError, serenity_prelude as serenity};
+
+use crate::{DiscordState, engine::GameError};
+
+#[derive(Debug)]
+pub enum Error {
+    Serenity(Box<serenity::Error>),
+    /// config.yaml is missing or invalid
+    Config(String),
+    /// The game state or message log couldn't be read or written
+    Persistence(String),
+    /// An argument that doesn't make sense, eg. a cottage that doesn't exist
+    Validation(String),
+    /// Whoever used the command isn't allowed to
+    Permission(String),
+    /// The rules don't allow what was asked for
+    Game(GameError),
+}
+
+impl Error {
+    /// What to tell whoever ran into this, without internals they can't do anything about
+    pub fn user_message(&self) -> String {
+        match self {
+            Error::Serenity(_) => {
+                "Something went wrong talking to Discord, please try again".to_owned()
+            }
+            Error::Config(_) => "The bot is misconfigured, let the server admins know".to_owned(),
+            Error::Persistence(_) => {
+                "The game couldn't be saved, this change will be lost if the bot restarts"
+                    .to_owned()
+            }
+            e => e.to_string(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Serenity(e) => write!(f, "Discord error: {e}"),
+            Error::Config(e) => write!(f, "Invalid config.yaml: {e}"),
+            Error::Persistence(e) => write!(f, "{e}"),
+            Error::Validation(e) | Error::Permission(e) => write!(f, "{e}"),
+            Error::Game(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<serenity::Error> for Error {
+    fn from(value: serenity::Error) -> Self {
+        Error::Serenity(Box::new(value))
+    }
+}
+
+impl From<GameError> for Error {
+    fn from(value: GameError) -> Self {
+        match value {
+            GameError::NoSuchCottage { .. } => Error::Validation(value.to_string()),
+            e => Error::Game(e),
+        }
+    }
+}
+
+/// Replies to failed commands with what went wrong, only visible to whoever used the command
+pub async fn on_error(err: FrameworkError<'_, DiscordState, Error>) {
+    match err {
+        FrameworkError::Command { error, ctx, .. }
+        | FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+            ..
+        } => {
+            if let Error::Serenity(_) | Error::Config(_) | Error::Persistence(_) = error {
+                println!("Error in /{}: {error}", ctx.command().name);
+            }
+            let reply = CreateReply::default()
+                .ephemeral(true)
+                .content(error.user_message());
+            if let Err(e) = ctx.send(reply).await {
+                println!("Failed to report an error in /{}: {e}", ctx.command().name);
+            }
+        }
+        FrameworkError::CommandCheckFailed { error: None, .. } => (),
+        err => {
+            if let Err(e) = poise::builtins::on_error(err).await {
+                println!("Failed to handle an error: {e}");
+            }
+        }
+    }
+}
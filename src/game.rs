@@ -0,0 +1,1484 @@
+//! Plays the game on a server: applies the rules from [`engine`] to the shared state and keeps
+//! the messages, roles and channels on the [`Gateway`] in sync with it
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::SystemTime,
+};
+
+use botc_discord_bot::{
+    message_log::read_all_segments,
+    transcript::{TranscriptFormat, TranscriptOptions, build_transcript},
+};
+use poise::{
+    futures_util::future::{join_all, try_join_all},
+    serenity_prelude::{
+        ButtonStyle, ChannelId, CreateButton, MessageId, PermissionOverwrite,
+        PermissionOverwriteType, Permissions, ReactionType, UserId,
+    },
+};
+use rand::seq::SliceRandom;
+use tokio::sync::RwLock;
+
+use crate::{
+    Config, Error,
+    deadlines::{Deadline, VoteDeadlines},
+    engine::{self, GameEvent},
+    gateway::{Gateway, OutgoingMessage},
+    modifiers::{ReminderMap, VoteModifiers},
+    rules::{GameOver, LifeEvent, check_game_over},
+    state::{
+        Character, CottageNumber, DeadState, FormatMention, Phase, PlayerMap, PrintSignUps, State,
+        Statement, Vote, VoteKind, VoteThread, Whisper, vote_message,
+    },
+    town_square::town_square_attachment,
+};
+
+/// What a player and the storytellers may do in a cottage channel
+pub const COTTAGE_ACCESS: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::SEND_MESSAGES)
+    .union(Permissions::READ_MESSAGE_HISTORY)
+    .union(Permissions::ATTACH_FILES);
+
+/// Longest accusation or defense, so it still fits in an embed field
+pub const MAX_STATEMENT_LENGTH: usize = 1000;
+
+pub struct Nomination {
+    pub nominator: UserId,
+    pub nominee: UserId,
+    pub description: String,
+    pub kind: VoteKind,
+    pub secret: bool,
+    /// Open a thread on the vote message for the accusation and defense
+    pub thread: bool,
+}
+
+/// The nomination line everyone sees first, the vote is rendered into the message afterwards so
+/// only the nominator and nominee get pinged
+fn nomination_message(vote: &Vote) -> OutgoingMessage {
+    OutgoingMessage::new(format!(
+        "{} has {} {}",
+        FormatMention(vote.nominator),
+        match vote.kind {
+            VoteKind::Execution => "nominated",
+            VoteKind::Exile => "called for the exile of",
+        },
+        FormatMention(vote.nominee)
+    ))
+    .button(
+        CreateButton::new(Button::Hand { raised: true }.custom_id())
+            .label("Hand Up")
+            .emoji(ReactionType::Unicode("🙋".to_string())),
+    )
+    .button(
+        CreateButton::new(Button::Hand { raised: false }.custom_id())
+            .label("Hand Down")
+            .emoji('🙅'),
+    )
+    .button(
+        CreateButton::new(Button::WriteStatement(Statement::Accusation).custom_id())
+            .label("Accusation")
+            .style(ButtonStyle::Secondary),
+    )
+    .button(
+        CreateButton::new(Button::WriteStatement(Statement::Defense).custom_id())
+            .label("Defense")
+            .style(ButtonStyle::Secondary),
+    )
+}
+
+/// Who gets to make a statement: the nominator accuses, the nominee defends
+fn statement_author(vote: &Vote, statement: Statement) -> UserId {
+    match statement {
+        Statement::Accusation => vote.nominator,
+        Statement::Defense => vote.nominee,
+    }
+}
+
+fn check_statement_author(
+    vote: &Vote,
+    statement: Statement,
+    user_id: UserId,
+    storyteller: bool,
+) -> Result<(), Error> {
+    let author = statement_author(vote, statement);
+    if storyteller {
+        Ok(())
+    } else if user_id != author {
+        Err(Error::Permission(format!(
+            "Only {} or a storyteller can set the {}",
+            FormatMention(author),
+            statement.name()
+        )))
+    } else if vote.deadlines.has_passed(Deadline::Statements) {
+        Err(engine::GameError::StatementsDue.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// The button storytellers press to accept or dismiss a statement posted in the thread of the
+/// vote posted as `vote`
+pub fn statement_button_id(vote: MessageId, statement: Statement, confirm: bool) -> String {
+    format!(
+        "statement_{}_{}_{vote}",
+        if confirm { "confirm" } else { "dismiss" },
+        statement.name()
+    )
+}
+
+/// The vote, the statement and whether it was accepted, for buttons made by
+/// [`statement_button_id`]
+pub fn parse_statement_button(custom_id: &str) -> Option<(MessageId, Statement, bool)> {
+    let rest = custom_id.strip_prefix("statement_")?;
+    let (action, rest) = rest.split_once('_')?;
+    let (statement, vote) = rest.split_once('_')?;
+    let confirm = match action {
+        "confirm" => true,
+        "dismiss" => false,
+        _ => return None,
+    };
+    let statement = [Statement::Accusation, Statement::Defense]
+        .into_iter()
+        .find(|i| i.name() == statement)?;
+    let vote = MessageId::new(vote.parse().ok().filter(|i| *i != 0)?);
+    Some((vote, statement, confirm))
+}
+
+/// The buttons a player is pinged with when the clock hand reaches them, for the vote posted as
+/// `vote`
+pub fn clock_hand_button_id(vote: MessageId, yes: bool) -> String {
+    format!("clock_hand_{}_{vote}", if yes { "yes" } else { "no" })
+}
+
+/// The vote and the player's choice, for buttons made by [`clock_hand_button_id`]
+pub fn parse_clock_hand_button(custom_id: &str) -> Option<(MessageId, bool)> {
+    let rest = custom_id.strip_prefix("clock_hand_")?;
+    let (choice, vote) = rest.split_once('_')?;
+    let yes = match choice {
+        "yes" => true,
+        "no" => false,
+        _ => return None,
+    };
+    Some((MessageId::new(vote.parse().ok().filter(|i| *i != 0)?), yes))
+}
+
+/// Every button the game posts, by what pressing it does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    /// Raises or lowers the presser's hand in the current vote
+    Hand {
+        raised: bool,
+    },
+    /// Opens the form the nominator or nominee writes their statement in
+    WriteStatement(Statement),
+    /// Votes from the ping a player gets when the clock hand reaches them
+    ClockHand {
+        vote: MessageId,
+        yes: bool,
+    },
+    /// Accepts or dismisses a statement posted in the thread of a vote
+    ResolveStatement {
+        vote: MessageId,
+        statement: Statement,
+        confirm: bool,
+    },
+    SignUp {
+        join: bool,
+    },
+}
+
+impl Button {
+    pub fn custom_id(self) -> String {
+        match self {
+            Button::Hand { raised: true } => "hand_up_button".to_owned(),
+            Button::Hand { raised: false } => "hand_down_button".to_owned(),
+            Button::WriteStatement(statement) => format!("{}_button", statement.name()),
+            Button::ClockHand { vote, yes } => clock_hand_button_id(vote, yes),
+            Button::ResolveStatement {
+                vote,
+                statement,
+                confirm,
+            } => statement_button_id(vote, statement, confirm),
+            Button::SignUp { join: true } => "signup_join_button".to_owned(),
+            Button::SignUp { join: false } => "signup_leave_button".to_owned(),
+        }
+    }
+
+    /// The button with this custom ID, `None` for buttons a command collects itself
+    pub fn parse(custom_id: &str) -> Option<Button> {
+        Some(match custom_id {
+            "hand_up_button" => Button::Hand { raised: true },
+            "hand_down_button" => Button::Hand { raised: false },
+            "accusation_button" => Button::WriteStatement(Statement::Accusation),
+            "defense_button" => Button::WriteStatement(Statement::Defense),
+            "signup_join_button" => Button::SignUp { join: true },
+            "signup_leave_button" => Button::SignUp { join: false },
+            custom_id => {
+                if let Some((vote, yes)) = parse_clock_hand_button(custom_id) {
+                    Button::ClockHand { vote, yes }
+                } else {
+                    let (vote, statement, confirm) = parse_statement_button(custom_id)?;
+                    Button::ResolveStatement {
+                        vote,
+                        statement,
+                        confirm,
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// The form a statement is written in, opened by [`Button::WriteStatement`]
+pub fn statement_modal_id(statement: Statement) -> String {
+    format!("{}_modal", statement.name())
+}
+
+/// The statement written in a form made by [`statement_modal_id`]
+pub fn parse_statement_modal(custom_id: &str) -> Option<Statement> {
+    [Statement::Accusation, Statement::Defense]
+        .into_iter()
+        .find(|i| statement_modal_id(*i) == custom_id)
+}
+
+/// What the vote message is rendered from, copied out of the state so nothing waits on the lock
+/// while the town square is drawn
+pub struct VoteSnapshot {
+    players: PlayerMap,
+    vote: Vote,
+    number_of_players: u32,
+    characters: HashMap<UserId, Character>,
+    reminders: ReminderMap,
+    names: HashMap<UserId, String>,
+    /// Which render this is, see [`State::vote_renders`]
+    render: u64,
+}
+
+impl VoteSnapshot {
+    /// The current vote, if there is one
+    pub fn of(state: &mut State) -> Option<VoteSnapshot> {
+        let vote = state.current_vote.clone()?;
+        state.vote_renders += 1;
+        Some(VoteSnapshot {
+            players: state.players.clone(),
+            vote,
+            number_of_players: state.number_of_players,
+            characters: state.characters.clone(),
+            reminders: state.reminders.clone(),
+            names: state.display_names.clone(),
+            render: state.vote_renders,
+        })
+    }
+}
+
+pub struct Game<'a, G> {
+    pub gateway: G,
+    pub config: &'a Config,
+    pub state: &'a RwLock<State>,
+}
+
+impl<G: Gateway> Game<'_, G> {
+    /// The full contents of the vote message, in the configured style. Without a town square
+    /// the attachment is left out, which clears an earlier one.
+    async fn vote_message(&self, mut snapshot: VoteSnapshot) -> OutgoingMessage {
+        let town_square = if self.config.town_square.enabled {
+            self.look_up_names(&mut snapshot).await;
+            town_square_attachment(
+                &self.config.town_square,
+                &snapshot.players,
+                &snapshot.vote,
+                snapshot.number_of_players,
+                &snapshot.names,
+            )
+            .await
+        } else {
+            None
+        };
+        vote_message(
+            &snapshot.players,
+            &snapshot.vote,
+            snapshot.number_of_players,
+            &VoteModifiers {
+                characters: &snapshot.characters,
+                reminders: &snapshot.reminders,
+            },
+            self.config.message_style,
+            town_square,
+        )
+    }
+
+    /// Fills in the names of players the state doesn't know yet and remembers them
+    async fn look_up_names(&self, snapshot: &mut VoteSnapshot) {
+        let missing: Vec<_> = snapshot
+            .players
+            .values()
+            .map(|(user_id, _)| *user_id)
+            .filter(|i| !snapshot.names.contains_key(i))
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        let names = join_all(missing.iter().map(|i| self.gateway.display_name(*i))).await;
+        let found: Vec<_> = missing
+            .into_iter()
+            .zip(names)
+            .filter_map(|(user_id, name)| Some((user_id, name.ok()?)))
+            .collect();
+        self.state
+            .write()
+            .await
+            .display_names
+            .extend(found.iter().cloned());
+        snapshot.names.extend(found);
+    }
+
+    /// Updates the vote message to match a snapshot of the vote, call this after releasing the
+    /// state
+    pub async fn render_vote(&self, snapshot: Option<VoteSnapshot>) -> Result<(), Error> {
+        let Some(snapshot) = snapshot else {
+            return Ok(());
+        };
+
+        let (channel_id, message_id, render) = (
+            snapshot.vote.channel_id,
+            snapshot.vote.message_id,
+            snapshot.render,
+        );
+        let message = self.vote_message(snapshot).await;
+        // A newer render started meanwhile and will show the vote as it is now
+        if self.state.read().await.vote_renders != render {
+            return Ok(());
+        }
+        self.gateway
+            .edit_message(channel_id, message_id, message)
+            .await
+    }
+
+    /// Applies a change to the game and updates the vote message to match
+    pub async fn mutate_vote<T>(
+        &self,
+        callback: impl FnOnce(&mut State) -> Result<T, engine::GameError>,
+    ) -> Result<T, Error> {
+        let mut state = self.state.write().await;
+        let result = callback(&mut state)?;
+        state.save()?;
+        let snapshot = VoteSnapshot::of(&mut state);
+        drop(state);
+
+        self.render_vote(snapshot).await?;
+        Ok(result)
+    }
+
+    /// Reads the dead and ghost vote roles of every seated player
+    pub async fn dead_state(
+        &self,
+        players: &PlayerMap,
+    ) -> Result<HashMap<UserId, DeadState>, Error> {
+        // One request per player, so they all go out at once
+        let user_ids: Vec<_> = players.values().map(|(user_id, _)| *user_id).collect();
+        let roles = try_join_all(user_ids.iter().map(|i| self.gateway.member_roles(*i))).await?;
+
+        let mut dead_status = HashMap::<UserId, DeadState>::new();
+        for (user_id, roles) in user_ids.iter().zip(roles) {
+            let is_dead = roles.contains(&self.config.dead_role);
+            let has_dead_vote = roles.contains(&self.config.ghost_vote_available_role);
+
+            dead_status.insert(
+                *user_id,
+                if has_dead_vote {
+                    DeadState::DeadVoteAvailable
+                } else if is_dead {
+                    DeadState::DeadVoteUsed
+                } else {
+                    DeadState::Alive
+                },
+            );
+        }
+
+        Ok(dead_status)
+    }
+
+    /// Gives a player the dead and ghost vote roles
+    pub async fn mark_dead(&self, player_id: UserId) -> Result<(), Error> {
+        self.gateway
+            .add_roles(
+                player_id,
+                &[self.config.dead_role, self.config.ghost_vote_available_role],
+            )
+            .await
+    }
+
+    /// Creates a private channel for a player that only they and the storytellers can see
+    pub async fn provision_cottage(&self, player_id: UserId) -> Result<ChannelId, Error> {
+        let name = self.gateway.display_name(player_id).await?;
+        let channel_id = self
+            .gateway
+            .create_channel(
+                format!("cottage-{name}"),
+                self.config.cottage_category,
+                vec![
+                    PermissionOverwrite {
+                        allow: Permissions::empty(),
+                        deny: Permissions::VIEW_CHANNEL,
+                        kind: PermissionOverwriteType::Role(self.config.guild_id.everyone_role()),
+                    },
+                    PermissionOverwrite {
+                        allow: COTTAGE_ACCESS,
+                        deny: Permissions::empty(),
+                        kind: PermissionOverwriteType::Member(player_id),
+                    },
+                    PermissionOverwrite {
+                        allow: COTTAGE_ACCESS,
+                        deny: Permissions::empty(),
+                        kind: PermissionOverwriteType::Role(self.config.storyteller_role),
+                    },
+                ],
+            )
+            .await?;
+
+        let mut state = self.state.write().await;
+        state.provisioned_cottages.insert(channel_id);
+        state.save()?;
+
+        Ok(channel_id)
+    }
+
+    /// Seats a player, reusing their cottage channel or creating one if they don't have one yet
+    pub async fn seat_player(
+        &self,
+        cottage_number: u32,
+        player_id: UserId,
+        channel_id: Option<ChannelId>,
+        traveller: Option<bool>,
+    ) -> Result<(), Error> {
+        let state = self.state.read().await;
+        let cottage = engine::cottage(&state, cottage_number)?;
+        let existing_channel = state
+            .players
+            .values()
+            .find(|(user_id, _)| *user_id == player_id)
+            .map(|(_, channel_id)| *channel_id);
+        drop(state);
+
+        let channel_id = match channel_id.or(existing_channel) {
+            Some(channel_id) => channel_id,
+            None => self.provision_cottage(player_id).await?,
+        };
+
+        let mut state = self.state.write().await;
+        state
+            .players
+            .retain(|_, (user_id, _channel_id)| *user_id != player_id);
+        state.players.insert(cottage, (player_id, channel_id));
+        match traveller {
+            Some(true) => {
+                state.travellers.insert(player_id);
+            }
+            Some(false) => {
+                state.travellers.remove(&player_id);
+            }
+            None => (),
+        }
+        state.save()?;
+
+        Ok(())
+    }
+
+    /// Posts the vote message with its hand buttons in a channel and makes it the active vote
+    pub async fn start_vote(
+        &self,
+        channel_id: ChannelId,
+        nomination: Nomination,
+    ) -> Result<Vec<GameEvent>, Error> {
+        // A secret ballot that never finished still has ghost votes to take away
+        let spent = secret_ghost_votes(&*self.state.read().await);
+        self.remove_ghost_votes(&spent).await?;
+
+        let state = self.state.read().await;
+        let clock_hand = engine::check_nomination(&state, nomination.nominee, nomination.kind)?;
+        let dead_state = self.dead_state(&state.players).await?;
+        drop(state);
+
+        let mut vote = Vote {
+            kind: nomination.kind,
+            nominator: nomination.nominator,
+            nominee: nomination.nominee,
+            description: nomination.description,
+            accusation: String::new(),
+            defense: String::new(),
+            clock_hand,
+            vote_state: HashMap::new(),
+            dead_state,
+            spent_ghost_votes: HashSet::new(),
+            butler_master_voting: None,
+            secret: nomination.secret,
+            message_id: MessageId::default(),
+            channel_id,
+            thread: None,
+            deadlines: VoteDeadlines::new(&self.config.deadlines, SystemTime::now()),
+        };
+        vote.message_id = self
+            .gateway
+            .send_message(channel_id, nomination_message(&vote))
+            .await?;
+
+        let mut state = self.state.write().await;
+        let previous_thread = vote_thread(&state);
+        let events = engine::open_vote(&mut state, vote);
+        state.save()?;
+        let first_voter = state.players.get(&clock_hand).map(|i| i.0);
+        let snapshot = VoteSnapshot::of(&mut state);
+        drop(state);
+        self.render_vote(snapshot).await?;
+        if let Some(first_voter) = first_voter {
+            self.ping_clock_hand(first_voter).await?;
+        }
+
+        self.archive_thread(previous_thread).await?;
+        if nomination.thread {
+            self.open_thread().await?;
+        }
+
+        Ok(events)
+    }
+
+    /// The current accusation or defense, if `user_id` is allowed to change it
+    pub async fn statement(
+        &self,
+        statement: Statement,
+        user_id: UserId,
+        storyteller: bool,
+    ) -> Result<String, Error> {
+        let mut state = self.state.write().await;
+        let vote = engine::active_vote(&mut state)?;
+        check_statement_author(vote, statement, user_id, storyteller)?;
+        Ok(vote.statement(statement).clone())
+    }
+
+    /// Sets the accusation or defense on behalf of the nominator, nominee or a storyteller
+    pub async fn set_statement(
+        &self,
+        statement: Statement,
+        user_id: UserId,
+        storyteller: bool,
+        text: String,
+    ) -> Result<(), Error> {
+        if text.chars().count() > MAX_STATEMENT_LENGTH {
+            return Err(Error::Validation(format!(
+                "The {} can be at most {MAX_STATEMENT_LENGTH} characters long",
+                statement.name()
+            )));
+        }
+
+        let mut state = self.state.write().await;
+        let vote = engine::active_vote(&mut state)?;
+        check_statement_author(vote, statement, user_id, storyteller)?;
+        *vote.statement(statement) = text;
+        // Anything still waiting in the thread would overwrite this
+        if let Some(thread) = &mut vote.thread {
+            *thread.pending(statement) = None;
+        }
+        state.save()?;
+        let snapshot = VoteSnapshot::of(&mut state);
+        drop(state);
+
+        self.render_vote(snapshot).await
+    }
+
+    /// Opens a thread on the vote message where the nominator and nominee can make their case
+    pub async fn open_thread(&self) -> Result<ChannelId, Error> {
+        let state = self.state.read().await;
+        let vote = state
+            .current_vote
+            .as_ref()
+            .ok_or(engine::GameError::NoActiveVote)?;
+        let (channel_id, message_id, nominee) = (vote.channel_id, vote.message_id, vote.nominee);
+        drop(state);
+
+        let name = self.gateway.display_name(nominee).await?;
+        let thread_id = self
+            .gateway
+            .create_thread(channel_id, message_id, format!("Nomination of {name}"))
+            .await?;
+
+        let mut state = self.state.write().await;
+        if let Some(vote) = &mut state.current_vote {
+            vote.thread = Some(VoteThread {
+                thread_id,
+                pending_accusation: None,
+                pending_defense: None,
+            });
+        }
+        state.save()?;
+
+        Ok(thread_id)
+    }
+
+    async fn archive_thread(&self, thread_id: Option<ChannelId>) -> Result<(), Error> {
+        match thread_id {
+            Some(thread_id) => self.gateway.archive_thread(thread_id).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Counts a message one of the two players sent in their whisper thread
+    pub async fn whisper_message(
+        &self,
+        channel_id: ChannelId,
+        author: UserId,
+    ) -> Result<(), Error> {
+        let mut state = self.state.write().await;
+        let Some(whisper) = state
+            .whispers
+            .threads
+            .iter_mut()
+            .find(|i| i.thread_id == channel_id && i.players.contains(&author))
+        else {
+            return Ok(());
+        };
+        whisper.messages += 1;
+        state.save()
+    }
+
+    /// Offers the first message the nominator or nominee posts in the vote thread to the
+    /// storytellers as their accusation or defense
+    pub async fn thread_message(
+        &self,
+        channel_id: ChannelId,
+        author: UserId,
+        content: &str,
+    ) -> Result<(), Error> {
+        let mut state = self.state.write().await;
+        let Some(vote) = &mut state.current_vote else {
+            return Ok(());
+        };
+        if content.trim().is_empty()
+            || vote.thread.as_ref().map(|i| i.thread_id) != Some(channel_id)
+        {
+            return Ok(());
+        }
+
+        let statement = if author == vote.nominator && vote.accusation.is_empty() {
+            Statement::Accusation
+        } else if author == vote.nominee && vote.defense.is_empty() {
+            Statement::Defense
+        } else {
+            return Ok(());
+        };
+        let Some(thread) = &mut vote.thread else {
+            return Ok(());
+        };
+        let pending = thread.pending(statement);
+        if pending.is_some() {
+            return Ok(());
+        }
+        *pending = Some(content.to_owned());
+        let vote_id = vote.message_id;
+        state.save()?;
+        drop(state);
+
+        let message = OutgoingMessage::new(format!(
+            "Storytellers, use this as the {}?\n> {}",
+            statement.name(),
+            content.replace('\n', "\n> ")
+        ))
+        .button(CreateButton::new(statement_button_id(vote_id, statement, true)).label("Use"))
+        .button(CreateButton::new(statement_button_id(vote_id, statement, false)).label("Dismiss"));
+        self.gateway.send_message(channel_id, message).await?;
+
+        Ok(())
+    }
+
+    /// Accepts or dismisses the statement waiting for a storyteller. Once dismissed, the next
+    /// message from the same player is offered instead.
+    pub async fn resolve_statement(
+        &self,
+        vote_message: MessageId,
+        statement: Statement,
+        confirm: bool,
+    ) -> Result<(), Error> {
+        let resolved = self
+            .mutate_vote(|state| {
+                let vote = engine::active_vote(state)?;
+                // The buttons of an earlier nomination don't decide this one's statements
+                if vote.message_id != vote_message {
+                    return Err(engine::GameError::VoteClosed);
+                }
+                let Some(text) = vote
+                    .thread
+                    .as_mut()
+                    .and_then(|i| i.pending(statement).take())
+                else {
+                    return Ok(false);
+                };
+                if confirm {
+                    *vote.statement(statement) = text;
+                }
+                Ok(true)
+            })
+            .await?;
+
+        if resolved {
+            Ok(())
+        } else {
+            Err(Error::Validation(format!(
+                "There is no {} waiting to be confirmed",
+                statement.name()
+            )))
+        }
+    }
+
+    /// Brings the vote message back in line with the saved vote after a restart. The message is
+    /// posted again if it was deleted while the bot was offline.
+    pub async fn restore_vote(&self) -> Result<(), Error> {
+        let state = self.state.read().await;
+        let Some(vote) = &state.current_vote else {
+            return Ok(());
+        };
+        let (channel_id, message_id) = (vote.channel_id, vote.message_id);
+        let nomination = nomination_message(vote);
+        drop(state);
+
+        if !self.gateway.message_exists(channel_id, message_id).await? {
+            let new_message_id = self.gateway.send_message(channel_id, nomination).await?;
+            let mut state = self.state.write().await;
+            // Unless a new vote was started in the meantime
+            if let Some(vote) = &mut state.current_vote
+                && vote.message_id == message_id
+            {
+                vote.message_id = new_message_id;
+                state.save()?;
+            }
+        }
+
+        let Some(snapshot) = VoteSnapshot::of(&mut *self.state.write().await) else {
+            return Ok(());
+        };
+
+        // Editing with buttons replaces the old ones, which might be from an older version
+        let buttons = nomination_message(&snapshot.vote).buttons;
+        let (channel_id, message_id) = (snapshot.vote.channel_id, snapshot.vote.message_id);
+        let mut message = self.vote_message(snapshot).await;
+        message.buttons = buttons;
+        self.gateway
+            .edit_message(channel_id, message_id, message)
+            .await
+    }
+
+    /// Lets a player know in their cottage that it's their turn to vote, unless they opted out
+    async fn ping_clock_hand(&self, player: UserId) -> Result<(), Error> {
+        let state = self.state.read().await;
+        let Some(vote) = &state.current_vote else {
+            return Ok(());
+        };
+        let cottage_channel = state
+            .players
+            .values()
+            .find(|(user_id, _)| *user_id == player)
+            .map(|(_, channel_id)| *channel_id);
+        let Some(channel_id) = cottage_channel else {
+            return Ok(());
+        };
+        if state.clock_hand_ping_opt_outs.contains(&player) {
+            return Ok(());
+        }
+
+        let message = OutgoingMessage::new(format!(
+            "{} the clock hand is on you for the nomination of {}, how do you vote?",
+            FormatMention(player),
+            FormatMention(vote.nominee)
+        ))
+        .button(
+            CreateButton::new(clock_hand_button_id(vote.message_id, true))
+                .label("Yes")
+                .style(ButtonStyle::Success),
+        )
+        .button(
+            CreateButton::new(clock_hand_button_id(vote.message_id, false))
+                .label("No")
+                .style(ButtonStyle::Danger),
+        );
+        drop(state);
+
+        self.gateway.send_message(channel_id, message).await?;
+        Ok(())
+    }
+
+    /// Locks hands and lets the storytellers know once a deadline of the active vote passes
+    pub async fn check_deadlines(&self, now: SystemTime) -> Result<Vec<GameEvent>, Error> {
+        let mut state = self.state.write().await;
+        let events = engine::pass_deadlines(&mut state, now);
+        let Some(vote) = state.current_vote.as_ref().filter(|_| !events.is_empty()) else {
+            return Ok(events);
+        };
+        let channel_id = vote.channel_id;
+        state.save()?;
+        let snapshot = VoteSnapshot::of(&mut state);
+        drop(state);
+        self.render_vote(snapshot).await?;
+
+        for event in &events {
+            if let GameEvent::DeadlinePassed { deadline, nominee } = event {
+                let what = match deadline {
+                    Deadline::Statements => "The accusation and defense are due",
+                    Deadline::Hands => "Hands are now locked",
+                    Deadline::Vote => "The vote is due",
+                };
+                let message = OutgoingMessage::new(format!(
+                    "<@&{}> {what} for the nomination of {}",
+                    self.config.storyteller_role,
+                    FormatMention(*nominee)
+                ));
+                self.gateway.send_message(channel_id, message).await?;
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Raises or lowers a player's hand, whether they pressed a button or a storyteller did it
+    pub async fn set_hand(&self, voter: UserId, raised: bool) -> Result<Vec<GameEvent>, Error> {
+        self.mutate_vote(|state| engine::set_hand(state, voter, raised))
+            .await
+    }
+
+    /// Locks in the vote under the clock hand, taking away a spent ghost vote
+    pub async fn cast_vote(&self, yes: bool) -> Result<Vec<GameEvent>, Error> {
+        self.record_vote(|state| engine::cast_vote(state, yes))
+            .await
+    }
+
+    /// Locks in a player's vote from the buttons they were pinged with, which belong to the vote
+    /// posted as `vote`
+    pub async fn cast_own_vote(
+        &self,
+        vote: MessageId,
+        voter: UserId,
+        yes: bool,
+    ) -> Result<Vec<GameEvent>, Error> {
+        self.record_vote(|state| engine::cast_own_vote(state, vote, voter, yes))
+            .await
+    }
+
+    async fn record_vote(
+        &self,
+        callback: impl FnOnce(&mut State) -> Result<Vec<GameEvent>, engine::GameError>,
+    ) -> Result<Vec<GameEvent>, Error> {
+        let events = self.mutate_vote(callback).await?;
+        let complete = events.contains(&GameEvent::VoteComplete);
+        let secret = self
+            .state
+            .read()
+            .await
+            .current_vote
+            .as_ref()
+            .is_some_and(|i| i.secret);
+
+        for event in &events {
+            match event {
+                // On a secret ballot the role going away would give the vote away
+                GameEvent::GhostVoteUsed { voter } if !secret => {
+                    self.remove_ghost_votes(&[*voter]).await?;
+                }
+                GameEvent::VoteComplete => {
+                    if secret {
+                        self.reveal_ghost_votes().await?;
+                    }
+                    let thread_id = vote_thread(&*self.state.read().await);
+                    self.archive_thread(thread_id).await?;
+                }
+                GameEvent::ClockHandMoved { player, .. } if !complete => {
+                    self.ping_clock_hand(*player).await?;
+                }
+                _ => (),
+            }
+        }
+
+        Ok(events)
+    }
+
+    async fn remove_ghost_votes(&self, voters: &[UserId]) -> Result<(), Error> {
+        for voter in voters {
+            self.gateway
+                .remove_roles(*voter, &[self.config.ghost_vote_available_role])
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Takes the ghost vote role from everyone who spent it in the current vote, once a secret
+    /// ballot is revealed
+    pub async fn reveal_ghost_votes(&self) -> Result<(), Error> {
+        let spent: Vec<_> = self
+            .state
+            .read()
+            .await
+            .current_vote
+            .iter()
+            .flat_map(|i| i.spent_ghost_votes.iter().copied())
+            .collect();
+        self.remove_ghost_votes(&spent).await
+    }
+
+    /// Announces how many votes a secret ballot got where the vote was posted, and with
+    /// `full_ballot` shows how everyone voted on the vote message
+    pub async fn reveal_votes(&self, full_ballot: bool) -> Result<(), Error> {
+        let channel_id = self
+            .mutate_vote(|state| {
+                let vote = engine::active_vote(state)?;
+                if full_ballot {
+                    vote.secret = false;
+                }
+                Ok(vote.channel_id)
+            })
+            .await?;
+
+        self.reveal_ghost_votes().await?;
+
+        let state = self.state.read().await;
+        let (count, finished) = state
+            .current_vote
+            .as_ref()
+            .map(|vote| {
+                (
+                    state.vote_modifiers().tally(vote).votes,
+                    vote.is_finished(&state.players),
+                )
+            })
+            .unwrap_or_default();
+        drop(state);
+
+        let content = if finished {
+            format!("The vote ended with **{count}** votes")
+        } else {
+            format!("The vote stands at **{count}** votes so far")
+        };
+        self.gateway
+            .send_message(channel_id, OutgoingMessage::new(content))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn start_day(&self) -> Result<Vec<GameEvent>, Error> {
+        let mut state = self.state.write().await;
+        let events = engine::start_day(&mut state, SystemTime::now());
+        state.save()?;
+        Ok(events)
+    }
+
+    /// Ends the day, marking whoever was executed as dead
+    pub async fn end_day(&self) -> Result<Vec<GameEvent>, Error> {
+        let mut state = self.state.write().await;
+        let thread_id = vote_thread(&state);
+        let spent = secret_ghost_votes(&state);
+        let events = engine::end_day(&mut state)?;
+        state.save()?;
+        drop(state);
+
+        self.remove_ghost_votes(&spent).await?;
+        self.archive_thread(thread_id).await?;
+
+        for event in &events {
+            if let GameEvent::Executed { player, .. } = event {
+                self.mark_dead(*player).await?;
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Everyone seated who doesn't have the dead role
+    pub async fn alive_players(&self) -> Result<Vec<UserId>, Error> {
+        let state = self.state.read().await;
+        let dead_state = self.dead_state(&state.players).await?;
+        Ok(state
+            .players
+            .values()
+            .map(|(user_id, _)| *user_id)
+            .filter(|i| matches!(dead_state.get(i), None | Some(DeadState::Alive)))
+            .collect())
+    }
+
+    /// How the game ended, if a death or execution ended it
+    pub async fn game_over(
+        &self,
+        dead_player: Option<UserId>,
+        event: LifeEvent,
+    ) -> Result<Option<GameOver>, Error> {
+        let mut alive = self.alive_players().await?;
+        // The role update might not have reached us yet
+        alive.retain(|i| Some(*i) != dead_player);
+        let state = self.state.read().await;
+        Ok(check_game_over(&state, &alive, event))
+    }
+
+    /// Ends the game and announces why
+    pub async fn end_game(&self, channel_id: ChannelId, game_over: GameOver) -> Result<(), Error> {
+        let mut state = self.state.write().await;
+        state.phase = Phase::GameOver;
+        state.timeline.ended = Some(SystemTime::now());
+        state.save()?;
+        drop(state);
+
+        self.gateway
+            .send_message(
+                channel_id,
+                OutgoingMessage::new(format!("**Game Over!** {game_over}")),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Renders the game from the message log, optionally only `channel`. Cottages and whispers
+    /// are left out unless `reveal_cottages` is set, which is only allowed once the game is over.
+    pub async fn transcript(
+        &self,
+        format: TranscriptFormat,
+        channel: Option<ChannelId>,
+        reveal_cottages: bool,
+    ) -> Result<String, Error> {
+        let state = self.state.read().await;
+        if reveal_cottages && state.phase != Phase::GameOver {
+            return Err(Error::Validation(
+                "Cottages can only be revealed once the game is over".to_owned(),
+            ));
+        }
+        let timeline = state.timeline.clone();
+        // Cottages seated earlier in the game are read from the log
+        let cottage_channels: HashSet<_> = state
+            .players
+            .values()
+            .map(|(_, i)| *i)
+            .chain(state.whispers.threads.iter().map(|i| i.thread_id))
+            .collect();
+        drop(state);
+
+        let channel_names = self.gateway.channel_names().await?;
+
+        let path = self.config.log.path.clone();
+        // Reading and decompressing every segment of the log takes a while
+        tokio::task::spawn_blocking(move || {
+            let records: Vec<_> = read_all_segments(&path)
+                .map(|records| records.filter_map(Result::ok).collect())
+                .unwrap_or_default();
+
+            build_transcript(
+                records,
+                TranscriptOptions {
+                    timeline,
+                    channels: channel.into_iter().collect(),
+                    cottage_channels,
+                    reveal_cottages,
+                    channel_names,
+                },
+            )
+            .render(format)
+        })
+        .await
+        .map_err(|e| Error::Persistence(format!("Failed to export the transcript: {e}")))
+    }
+
+    /// Opens the private thread between two players, or reopens the one they already have.
+    /// Whispers are announced in `channel_id`, where the thread is also started unless whispers
+    /// have their own channel.
+    pub async fn whisper(
+        &self,
+        channel_id: ChannelId,
+        author: UserId,
+        player_id: UserId,
+    ) -> Result<ChannelId, Error> {
+        let state = self.state.read().await;
+        if !state.is_seated(author) {
+            return Err(Error::Validation(
+                "You must be seated to whisper".to_owned(),
+            ));
+        } else if !state.is_seated(player_id) {
+            return Err(engine::GameError::NotSeated(player_id).into());
+        } else if player_id == author {
+            return Err(Error::Validation(
+                "You can't whisper to yourself".to_owned(),
+            ));
+        } else if state.phase == Phase::Night && state.whispers.close_at_night {
+            return Err(Error::Validation(
+                "Whispers are closed for the night".to_owned(),
+            ));
+        }
+        let existing_thread = state
+            .whispers
+            .threads
+            .iter()
+            .find(|i| i.is_between(author, player_id))
+            .map(|i| i.thread_id);
+        let announce = state.whispers.announce;
+        drop(state);
+
+        let thread_id = match existing_thread {
+            Some(thread_id) => {
+                self.gateway.unarchive_thread(thread_id).await?;
+                thread_id
+            }
+            None => self.open_whisper(channel_id, author, player_id).await?,
+        };
+
+        let mut state = self.state.write().await;
+        let whispers = &mut state.whispers.threads;
+        let whisper = match whispers.iter_mut().position(|i| i.thread_id == thread_id) {
+            Some(index) => &mut whispers[index],
+            None => {
+                whispers.push(Whisper {
+                    players: [author, player_id],
+                    thread_id,
+                    started: vec![],
+                    messages: 0,
+                });
+                whispers.last_mut().unwrap()
+            }
+        };
+        whisper.started.push(SystemTime::now());
+        state.save()?;
+        drop(state);
+
+        if announce {
+            let message = OutgoingMessage::new(format!(
+                "{} is whispering to {}",
+                FormatMention(author),
+                FormatMention(player_id)
+            ));
+            self.gateway.send_message(channel_id, message).await?;
+        }
+
+        Ok(thread_id)
+    }
+
+    /// Starts a new whisper thread with both players and every storyteller in it
+    async fn open_whisper(
+        &self,
+        channel_id: ChannelId,
+        author: UserId,
+        player_id: UserId,
+    ) -> Result<ChannelId, Error> {
+        let name = format!(
+            "Whisper: {} & {}",
+            self.gateway.display_name(author).await?,
+            self.gateway.display_name(player_id).await?
+        );
+        let thread_id = self
+            .gateway
+            .create_private_thread(self.config.whisper_channel.unwrap_or(channel_id), name)
+            .await?;
+        self.gateway.add_thread_member(thread_id, author).await?;
+        self.gateway.add_thread_member(thread_id, player_id).await?;
+
+        let intro = format!(
+            "{} is whispering to {}",
+            FormatMention(author),
+            FormatMention(player_id)
+        );
+        let message_id = self
+            .gateway
+            .send_message(thread_id, OutgoingMessage::new(&intro))
+            .await?;
+        // Editing in a role mention adds every storyteller to the thread without pinging them
+        let message = OutgoingMessage::new(format!(
+            "{intro}, <@&{}> can read along",
+            self.config.storyteller_role
+        ));
+        self.gateway
+            .edit_message(thread_id, message_id, message)
+            .await?;
+
+        Ok(thread_id)
+    }
+
+    /// Locks every whisper thread, eg. when night falls, or reopens them
+    pub async fn lock_whispers(&self, locked: bool) -> Result<(), Error> {
+        let threads: Vec<_> = self
+            .state
+            .read()
+            .await
+            .whispers
+            .threads
+            .iter()
+            .map(|i| i.thread_id)
+            .collect();
+
+        for thread in threads {
+            self.gateway.lock_thread(thread, locked).await?;
+        }
+        Ok(())
+    }
+
+    /// Deletes or archives every cottage channel the bot created. Returns how many were closed
+    /// and the ones that couldn't be, which stay in the state so closing them can be retried.
+    pub async fn close_cottages(
+        &self,
+        mode: CottageTeardown,
+    ) -> Result<(usize, Vec<ChannelId>), Error> {
+        // Tearing down takes a request per cottage, nothing should wait on the state meanwhile
+        let state = self.state.read().await;
+        let cottages: Vec<_> = state
+            .provisioned_cottages
+            .iter()
+            .map(|channel_id| {
+                let player = state
+                    .players
+                    .values()
+                    .find(|(_, i)| i == channel_id)
+                    .map(|(user_id, _)| *user_id);
+                (*channel_id, player)
+            })
+            .collect();
+        drop(state);
+
+        let mut closed = 0;
+        let mut failures = vec![];
+        for (channel_id, player) in cottages {
+            let result = match mode {
+                CottageTeardown::Delete => self.gateway.delete_channel(channel_id).await,
+                CottageTeardown::Archive => {
+                    self.gateway
+                        .edit_channel(
+                            channel_id,
+                            self.config.cottage_archive_category,
+                            self.archived_cottage(player),
+                        )
+                        .await
+                }
+            };
+
+            // Keep going past a cottage that couldn't be closed, the state follows every one
+            // that did
+            match result {
+                Ok(()) => {
+                    closed += 1;
+                    let mut state = self.state.write().await;
+                    state.provisioned_cottages.remove(&channel_id);
+                    if mode == CottageTeardown::Delete {
+                        state.players.retain(|_, (_, i)| *i != channel_id);
+                    }
+                    state.save()?;
+                }
+                Err(e) => {
+                    println!("Failed to close cottage {channel_id}: {e}");
+                    failures.push(channel_id);
+                }
+            }
+        }
+
+        Ok((closed, failures))
+    }
+
+    /// Permissions of an archived cottage, that its player can still read but not write in
+    fn archived_cottage(&self, player: Option<UserId>) -> Vec<PermissionOverwrite> {
+        let mut permissions = vec![
+            PermissionOverwrite {
+                allow: Permissions::empty(),
+                deny: Permissions::VIEW_CHANNEL,
+                kind: PermissionOverwriteType::Role(self.config.guild_id.everyone_role()),
+            },
+            PermissionOverwrite {
+                allow: Permissions::VIEW_CHANNEL | Permissions::READ_MESSAGE_HISTORY,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Role(self.config.storyteller_role),
+            },
+        ];
+        if let Some(player) = player {
+            permissions.push(PermissionOverwrite {
+                allow: Permissions::VIEW_CHANNEL | Permissions::READ_MESSAGE_HISTORY,
+                deny: Permissions::SEND_MESSAGES,
+                kind: PermissionOverwriteType::Member(player),
+            });
+        }
+        permissions
+    }
+
+    /// Posts the message players use to sign up for the next game
+    pub async fn open_signups(&self, channel_id: ChannelId) -> Result<(), Error> {
+        let message = OutgoingMessage::new("**Sign-ups**")
+            .button(
+                CreateButton::new(Button::SignUp { join: true }.custom_id())
+                    .label("Join")
+                    .style(ButtonStyle::Success),
+            )
+            .button(
+                CreateButton::new(Button::SignUp { join: false }.custom_id())
+                    .label("Leave")
+                    .style(ButtonStyle::Secondary),
+            );
+        let message_id = self.gateway.send_message(channel_id, message).await?;
+
+        let mut state = self.state.write().await;
+        state.signups.message = Some((channel_id, message_id));
+        state.save()?;
+        drop(state);
+
+        // Edit rather than send the list so players already in the queue aren't pinged
+        self.update_signups().await
+    }
+
+    /// Adds a player to the end of the sign-up queue, unless they already have a cottage
+    pub async fn join_signups(&self, user_id: UserId) -> Result<(), Error> {
+        let mut state = self.state.write().await;
+        if state.is_seated(user_id) {
+            return Err(Error::Validation("You already have a cottage".to_owned()));
+        }
+        if !state.signups.queue.contains(&user_id) {
+            state.signups.queue.push(user_id);
+            state.save()?;
+        }
+        drop(state);
+
+        self.update_signups().await
+    }
+
+    /// Takes a player off the sign-up queue
+    pub async fn leave_signups(&self, user_id: UserId) -> Result<(), Error> {
+        let mut state = self.state.write().await;
+        state.signups.queue.retain(|i| *i != user_id);
+        state.save()?;
+        drop(state);
+
+        self.update_signups().await
+    }
+
+    /// Seats the first players in the sign-up queue in the empty cottages and creates their
+    /// channels
+    pub async fn seat_signups(&self, order: SeatingOrder) -> Result<(), Error> {
+        let state = self.state.read().await;
+        let cottages = state.empty_cottages();
+        let mut players: Vec<_> = state
+            .signups
+            .queue
+            .iter()
+            .take(cottages.len())
+            .copied()
+            .collect();
+        drop(state);
+
+        if players.is_empty() {
+            return Err(Error::Validation(
+                "There is nobody to seat, or no empty cottage to seat them in".to_owned(),
+            ));
+        }
+        if order == SeatingOrder::Random {
+            players.shuffle(&mut rand::rng());
+        }
+
+        let mut channels = vec![];
+        for player in &players {
+            channels.push(self.provision_cottage(*player).await?);
+        }
+
+        let mut state = self.state.write().await;
+        for ((cottage, player), channel) in cottages.into_iter().zip(players).zip(channels) {
+            state.players.insert(cottage, (player, channel));
+            state.signups.queue.retain(|i| *i != player);
+        }
+        state.save()?;
+        drop(state);
+
+        self.update_signups().await
+    }
+
+    /// Updates the sign-up message to match the queue
+    pub async fn update_signups(&self) -> Result<(), Error> {
+        let state = self.state.read().await;
+        let Some((channel_id, message_id)) = state.signups.message else {
+            return Ok(());
+        };
+        let message = OutgoingMessage::new(PrintSignUps(&state).to_string());
+        drop(state);
+
+        self.gateway
+            .edit_message(channel_id, message_id, message)
+            .await
+    }
+
+    /// Replaces a player who dropped out with `new` or the first player on the waitlist, who
+    /// keeps their seat, character, vote and cottage. Returns who took over which cottage.
+    pub async fn substitute(
+        &self,
+        old: UserId,
+        new: Option<UserId>,
+    ) -> Result<(UserId, CottageNumber), Error> {
+        let mut state = self.state.write().await;
+        let new = match new.or_else(|| state.signups.queue.first().copied()) {
+            None => Err(Error::Validation("Nobody is on the waitlist".to_owned())),
+            Some(new) if state.is_seated(new) => Err(Error::Validation(format!(
+                "{} already has a cottage",
+                FormatMention(new)
+            ))),
+            Some(new) => Ok(new),
+        }?;
+        let (cottage, channel_id) = state
+            .substitute(old, new)
+            .ok_or(engine::GameError::NotSeated(old))?;
+        state.save()?;
+        let snapshot = VoteSnapshot::of(&mut state);
+        drop(state);
+        self.update_signups().await?;
+        self.render_vote(snapshot).await?;
+
+        let old_roles = self.gateway.member_roles(old).await?;
+        let life_roles: Vec<_> = [self.config.dead_role, self.config.ghost_vote_available_role]
+            .into_iter()
+            .filter(|i| old_roles.contains(i))
+            .collect();
+        if !life_roles.is_empty() {
+            self.gateway.add_roles(new, &life_roles).await?;
+            self.gateway.remove_roles(old, &life_roles).await?;
+        }
+
+        self.gateway
+            .set_permission(
+                channel_id,
+                PermissionOverwrite {
+                    allow: COTTAGE_ACCESS,
+                    deny: Permissions::empty(),
+                    kind: PermissionOverwriteType::Member(new),
+                },
+            )
+            .await?;
+        self.gateway
+            .remove_permission(channel_id, PermissionOverwriteType::Member(old))
+            .await?;
+
+        Ok((new, cottage))
+    }
+}
+
+#[derive(poise::ChoiceParameter, Clone, Copy, PartialEq, Eq)]
+pub enum CottageTeardown {
+    /// Delete the channels entirely
+    Delete,
+    /// Make the channels read only and move them to the archive category
+    Archive,
+}
+
+#[derive(poise::ChoiceParameter, Clone, Copy, PartialEq, Eq)]
+pub enum SeatingOrder {
+    #[name = "Join Order"]
+    JoinOrder,
+    Random,
+}
+
+fn vote_thread(state: &State) -> Option<ChannelId> {
+    state
+        .current_vote
+        .as_ref()?
+        .thread
+        .as_ref()
+        .map(|i| i.thread_id)
+}
+
+/// Ghost votes spent on a secret ballot that hasn't finished, whose roles are still there
+fn secret_ghost_votes(state: &State) -> Vec<UserId> {
+    state
+        .current_vote
+        .iter()
+        .filter(|i| i.secret && !i.is_finished(&state.players))
+        .flat_map(|i| i.spent_ghost_votes.iter().copied())
+        .collect()
+}
@@ -0,0 +1,700 @@
+//! The Discord operations the game needs, so games can be played against something other than
+//! a real server
+
+use std::{collections::HashMap, future::Future};
+
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, ChannelType, CreateActionRow, CreateAttachment, CreateButton,
+    CreateChannel, CreateEmbed, CreateMessage, CreateThread, EditAttachments, EditChannel,
+    EditMessage, EditThread, GuildId, MessageId, PermissionOverwrite, PermissionOverwriteType,
+    RoleId, UserId,
+};
+
+use crate::Error;
+
+/// A message to send or the new contents of one being edited
+#[derive(Default, Clone)]
+pub struct OutgoingMessage {
+    pub content: String,
+    pub embed: Option<CreateEmbed>,
+    /// Replaces every earlier attachment when editing
+    pub attachment: Option<CreateAttachment>,
+    pub buttons: Vec<CreateButton>,
+}
+
+impl OutgoingMessage {
+    pub fn new(content: impl Into<String>) -> OutgoingMessage {
+        OutgoingMessage {
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn embed(mut self, embed: CreateEmbed) -> OutgoingMessage {
+        self.embed = Some(embed);
+        self
+    }
+
+    pub fn attachment(mut self, attachment: Option<CreateAttachment>) -> OutgoingMessage {
+        self.attachment = attachment;
+        self
+    }
+
+    pub fn button(mut self, button: CreateButton) -> OutgoingMessage {
+        self.buttons.push(button);
+        self
+    }
+}
+
+pub trait Gateway: Sync {
+    fn send_message(
+        &self,
+        channel_id: ChannelId,
+        message: OutgoingMessage,
+    ) -> impl Future<Output = Result<MessageId, Error>> + Send;
+
+    fn edit_message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        message: OutgoingMessage,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Whether a message is still there, `false` if it or its channel was deleted
+    fn message_exists(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> impl Future<Output = Result<bool, Error>> + Send;
+
+    fn member_roles(
+        &self,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<RoleId>, Error>> + Send;
+
+    fn display_name(&self, user_id: UserId) -> impl Future<Output = Result<String, Error>> + Send;
+
+    fn add_roles(
+        &self,
+        user_id: UserId,
+        roles: &[RoleId],
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn remove_roles(
+        &self,
+        user_id: UserId,
+        roles: &[RoleId],
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Starts a public thread on a message
+    fn create_thread(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        name: String,
+    ) -> impl Future<Output = Result<ChannelId, Error>> + Send;
+
+    fn archive_thread(
+        &self,
+        thread_id: ChannelId,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn unarchive_thread(
+        &self,
+        thread_id: ChannelId,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Locks and archives a thread so nobody but moderators can post in it, or reopens it
+    fn lock_thread(
+        &self,
+        thread_id: ChannelId,
+        locked: bool,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Starts a private thread that only its members and moderators can see
+    fn create_private_thread(
+        &self,
+        channel_id: ChannelId,
+        name: String,
+    ) -> impl Future<Output = Result<ChannelId, Error>> + Send;
+
+    fn add_thread_member(
+        &self,
+        thread_id: ChannelId,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Creates a text channel, optionally inside a category
+    fn create_channel(
+        &self,
+        name: String,
+        category: Option<ChannelId>,
+        permissions: Vec<PermissionOverwrite>,
+    ) -> impl Future<Output = Result<ChannelId, Error>> + Send;
+
+    /// Replaces every permission on a channel, and moves it to another category if one is given
+    fn edit_channel(
+        &self,
+        channel_id: ChannelId,
+        category: Option<ChannelId>,
+        permissions: Vec<PermissionOverwrite>,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn delete_channel(
+        &self,
+        channel_id: ChannelId,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Adds or replaces the permissions of one role or member on a channel
+    fn set_permission(
+        &self,
+        channel_id: ChannelId,
+        permission: PermissionOverwrite,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn remove_permission(
+        &self,
+        channel_id: ChannelId,
+        kind: PermissionOverwriteType,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// The name of every channel on the server
+    fn channel_names(
+        &self,
+    ) -> impl Future<Output = Result<HashMap<ChannelId, String>, Error>> + Send;
+}
+
+pub struct SerenityGateway<'a> {
+    pub ctx: &'a serenity::Context,
+    pub guild: GuildId,
+}
+
+impl Gateway for SerenityGateway<'_> {
+    async fn send_message(
+        &self,
+        channel_id: ChannelId,
+        message: OutgoingMessage,
+    ) -> Result<MessageId, Error> {
+        let mut create = CreateMessage::new().content(message.content);
+        if let Some(embed) = message.embed {
+            create = create.embed(embed);
+        }
+        if let Some(attachment) = message.attachment {
+            create = create.add_file(attachment);
+        }
+        if !message.buttons.is_empty() {
+            create = create.components(vec![CreateActionRow::Buttons(message.buttons)]);
+        }
+        Ok(channel_id.send_message(self.ctx, create).await?.id)
+    }
+
+    async fn edit_message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        message: OutgoingMessage,
+    ) -> Result<(), Error> {
+        let mut edit = EditMessage::new().content(message.content);
+        if let Some(embed) = message.embed {
+            edit = edit.embed(embed);
+        }
+        // Leaving the attachment out clears the old one, eg. a town square that failed to render
+        let mut attachments = EditAttachments::new();
+        if let Some(attachment) = message.attachment {
+            attachments = attachments.add(attachment);
+        }
+        edit = edit.attachments(attachments);
+        if !message.buttons.is_empty() {
+            edit = edit.components(vec![CreateActionRow::Buttons(message.buttons)]);
+        }
+        channel_id.edit_message(self.ctx, message_id, edit).await?;
+        Ok(())
+    }
+
+    async fn message_exists(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<bool, Error> {
+        match channel_id.message(self.ctx, message_id).await {
+            Ok(_) => Ok(true),
+            Err(serenity::Error::Http(e)) if e.status_code().is_some_and(|i| i.as_u16() == 404) => {
+                Ok(false)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn member_roles(&self, user_id: UserId) -> Result<Vec<RoleId>, Error> {
+        Ok(self.guild.member(self.ctx, user_id).await?.roles)
+    }
+
+    async fn display_name(&self, user_id: UserId) -> Result<String, Error> {
+        Ok(self
+            .guild
+            .member(self.ctx, user_id)
+            .await?
+            .display_name()
+            .to_owned())
+    }
+
+    async fn add_roles(&self, user_id: UserId, roles: &[RoleId]) -> Result<(), Error> {
+        self.guild
+            .member(self.ctx, user_id)
+            .await?
+            .add_roles(self.ctx, roles)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_roles(&self, user_id: UserId, roles: &[RoleId]) -> Result<(), Error> {
+        self.guild
+            .member(self.ctx, user_id)
+            .await?
+            .remove_roles(self.ctx, roles)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_thread(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        name: String,
+    ) -> Result<ChannelId, Error> {
+        Ok(channel_id
+            .create_thread_from_message(self.ctx, message_id, CreateThread::new(name))
+            .await?
+            .id)
+    }
+
+    async fn archive_thread(&self, thread_id: ChannelId) -> Result<(), Error> {
+        thread_id
+            .edit_thread(self.ctx, EditThread::new().archived(true))
+            .await?;
+        Ok(())
+    }
+
+    async fn unarchive_thread(&self, thread_id: ChannelId) -> Result<(), Error> {
+        thread_id
+            .edit_thread(self.ctx, EditThread::new().archived(false))
+            .await?;
+        Ok(())
+    }
+
+    async fn lock_thread(&self, thread_id: ChannelId, locked: bool) -> Result<(), Error> {
+        thread_id
+            .edit_thread(self.ctx, EditThread::new().locked(locked).archived(locked))
+            .await?;
+        Ok(())
+    }
+
+    async fn create_private_thread(
+        &self,
+        channel_id: ChannelId,
+        name: String,
+    ) -> Result<ChannelId, Error> {
+        Ok(channel_id
+            .create_thread(
+                self.ctx,
+                CreateThread::new(name)
+                    .kind(ChannelType::PrivateThread)
+                    .invitable(false),
+            )
+            .await?
+            .id)
+    }
+
+    async fn add_thread_member(&self, thread_id: ChannelId, user_id: UserId) -> Result<(), Error> {
+        thread_id.add_thread_member(self.ctx, user_id).await?;
+        Ok(())
+    }
+
+    async fn create_channel(
+        &self,
+        name: String,
+        category: Option<ChannelId>,
+        permissions: Vec<PermissionOverwrite>,
+    ) -> Result<ChannelId, Error> {
+        let mut channel = CreateChannel::new(name)
+            .kind(ChannelType::Text)
+            .permissions(permissions);
+        if let Some(category) = category {
+            channel = channel.category(category);
+        }
+        Ok(self.guild.create_channel(self.ctx, channel).await?.id)
+    }
+
+    async fn edit_channel(
+        &self,
+        channel_id: ChannelId,
+        category: Option<ChannelId>,
+        permissions: Vec<PermissionOverwrite>,
+    ) -> Result<(), Error> {
+        let mut edit = EditChannel::new().permissions(permissions);
+        if let Some(category) = category {
+            edit = edit.category(category);
+        }
+        channel_id.edit(self.ctx, edit).await?;
+        Ok(())
+    }
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<(), Error> {
+        channel_id.delete(self.ctx).await?;
+        Ok(())
+    }
+
+    async fn set_permission(
+        &self,
+        channel_id: ChannelId,
+        permission: PermissionOverwrite,
+    ) -> Result<(), Error> {
+        channel_id.create_permission(self.ctx, permission).await?;
+        Ok(())
+    }
+
+    async fn remove_permission(
+        &self,
+        channel_id: ChannelId,
+        kind: PermissionOverwriteType,
+    ) -> Result<(), Error> {
+        channel_id.delete_permission(self.ctx, kind).await?;
+        Ok(())
+    }
+
+    async fn channel_names(&self) -> Result<HashMap<ChannelId, String>, Error> {
+        Ok(self
+            .guild
+            .channels(self.ctx)
+            .await?
+            .into_iter()
+            .map(|(id, channel)| (id, channel.name))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use std::{
+        collections::HashSet,
+        sync::{
+            Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+    };
+
+    use super::*;
+
+    /// A message as the fake server stores it
+    #[derive(Debug, Clone)]
+    pub struct SentMessage {
+        pub channel_id: ChannelId,
+        pub content: String,
+        pub embed: Option<serde_json::Value>,
+        pub has_attachment: bool,
+        pub buttons: Vec<String>,
+    }
+
+    impl SentMessage {
+        fn new(channel_id: ChannelId, message: OutgoingMessage) -> SentMessage {
+            SentMessage {
+                channel_id,
+                content: message.content,
+                embed: message.embed.map(|i| serde_json::to_value(i).unwrap()),
+                has_attachment: message.attachment.is_some(),
+                buttons: message
+                    .buttons
+                    .iter()
+                    .filter_map(|i| {
+                        serde_json::to_value(i).unwrap()["custom_id"]
+                            .as_str()
+                            .map(str::to_owned)
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct PrivateThread {
+        pub name: String,
+        pub members: HashSet<UserId>,
+        pub archived: bool,
+        pub locked: bool,
+    }
+
+    /// An in-memory server, every ID it hands out is unique
+    #[derive(Default)]
+    pub struct FakeGateway {
+        next_id: AtomicU64,
+        pub messages: Mutex<Vec<(MessageId, SentMessage)>>,
+        pub roles: Mutex<HashMap<UserId, HashSet<RoleId>>>,
+        pub channels: Mutex<Vec<(ChannelId, String, Vec<PermissionOverwrite>)>>,
+        /// Threads by the message they were started on, and whether they're archived
+        pub threads: Mutex<HashMap<MessageId, (ChannelId, String, bool)>>,
+        pub private_threads: Mutex<HashMap<ChannelId, PrivateThread>>,
+    }
+
+    impl FakeGateway {
+        fn id(&self) -> u64 {
+            self.next_id.fetch_add(1, Ordering::Relaxed) + 1_000_000
+        }
+
+        pub fn message(&self, message_id: MessageId) -> Option<SentMessage> {
+            self.messages
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(i, _)| *i == message_id)
+                .map(|(_, message)| message.clone())
+        }
+
+        pub fn delete_message(&self, message_id: MessageId) {
+            self.messages
+                .lock()
+                .unwrap()
+                .retain(|(i, _)| *i != message_id);
+        }
+
+        /// Changes the permissions of a channel the fake server created
+        fn edit_permissions(
+            &self,
+            channel_id: ChannelId,
+            edit: impl FnOnce(&mut Vec<PermissionOverwrite>),
+        ) -> Result<(), Error> {
+            let mut channels = self.channels.lock().unwrap();
+            match channels.iter_mut().find(|(i, _, _)| *i == channel_id) {
+                Some((_, _, permissions)) => {
+                    edit(permissions);
+                    Ok(())
+                }
+                None => Err(Error::Validation(format!("Unknown channel {channel_id}"))),
+            }
+        }
+
+        pub fn has_role(&self, user_id: UserId, role: RoleId) -> bool {
+            self.roles
+                .lock()
+                .unwrap()
+                .get(&user_id)
+                .is_some_and(|i| i.contains(&role))
+        }
+    }
+
+    impl Gateway for FakeGateway {
+        async fn send_message(
+            &self,
+            channel_id: ChannelId,
+            message: OutgoingMessage,
+        ) -> Result<MessageId, Error> {
+            let message_id = MessageId::new(self.id());
+            self.messages
+                .lock()
+                .unwrap()
+                .push((message_id, SentMessage::new(channel_id, message)));
+            Ok(message_id)
+        }
+
+        async fn edit_message(
+            &self,
+            channel_id: ChannelId,
+            message_id: MessageId,
+            message: OutgoingMessage,
+        ) -> Result<(), Error> {
+            let mut messages = self.messages.lock().unwrap();
+            let Some((_, sent)) = messages.iter_mut().find(|(i, _)| *i == message_id) else {
+                return Err(Error::Validation(format!("Unknown message {message_id}")));
+            };
+            let buttons = std::mem::take(&mut sent.buttons);
+            *sent = SentMessage::new(channel_id, message);
+            // Editing without components leaves the existing ones alone
+            if sent.buttons.is_empty() {
+                sent.buttons = buttons;
+            }
+            Ok(())
+        }
+
+        async fn message_exists(
+            &self,
+            _channel_id: ChannelId,
+            message_id: MessageId,
+        ) -> Result<bool, Error> {
+            Ok(self.message(message_id).is_some())
+        }
+
+        async fn member_roles(&self, user_id: UserId) -> Result<Vec<RoleId>, Error> {
+            Ok(self
+                .roles
+                .lock()
+                .unwrap()
+                .get(&user_id)
+                .map(|i| i.iter().copied().collect())
+                .unwrap_or_default())
+        }
+
+        async fn display_name(&self, user_id: UserId) -> Result<String, Error> {
+            Ok(format!("Player {user_id}"))
+        }
+
+        async fn add_roles(&self, user_id: UserId, roles: &[RoleId]) -> Result<(), Error> {
+            self.roles
+                .lock()
+                .unwrap()
+                .entry(user_id)
+                .or_default()
+                .extend(roles);
+            Ok(())
+        }
+
+        async fn remove_roles(&self, user_id: UserId, roles: &[RoleId]) -> Result<(), Error> {
+            if let Some(member_roles) = self.roles.lock().unwrap().get_mut(&user_id) {
+                member_roles.retain(|i| !roles.contains(i));
+            }
+            Ok(())
+        }
+
+        async fn create_thread(
+            &self,
+            _channel_id: ChannelId,
+            message_id: MessageId,
+            name: String,
+        ) -> Result<ChannelId, Error> {
+            let thread_id = ChannelId::new(self.id());
+            self.threads
+                .lock()
+                .unwrap()
+                .insert(message_id, (thread_id, name, false));
+            Ok(thread_id)
+        }
+
+        async fn archive_thread(&self, thread_id: ChannelId) -> Result<(), Error> {
+            for (i, _, archived) in self.threads.lock().unwrap().values_mut() {
+                if *i == thread_id {
+                    *archived = true;
+                }
+            }
+            if let Some(thread) = self.private_threads.lock().unwrap().get_mut(&thread_id) {
+                thread.archived = true;
+            }
+            Ok(())
+        }
+
+        async fn unarchive_thread(&self, thread_id: ChannelId) -> Result<(), Error> {
+            for (i, _, archived) in self.threads.lock().unwrap().values_mut() {
+                if *i == thread_id {
+                    *archived = false;
+                }
+            }
+            if let Some(thread) = self.private_threads.lock().unwrap().get_mut(&thread_id) {
+                thread.archived = false;
+            }
+            Ok(())
+        }
+
+        async fn lock_thread(&self, thread_id: ChannelId, locked: bool) -> Result<(), Error> {
+            match self.private_threads.lock().unwrap().get_mut(&thread_id) {
+                Some(thread) => {
+                    thread.locked = locked;
+                    thread.archived = locked;
+                    Ok(())
+                }
+                None => Err(Error::Validation(format!("Unknown thread {thread_id}"))),
+            }
+        }
+
+        async fn create_private_thread(
+            &self,
+            _channel_id: ChannelId,
+            name: String,
+        ) -> Result<ChannelId, Error> {
+            let thread_id = ChannelId::new(self.id());
+            self.private_threads.lock().unwrap().insert(
+                thread_id,
+                PrivateThread {
+                    name,
+                    members: HashSet::new(),
+                    archived: false,
+                    locked: false,
+                },
+            );
+            Ok(thread_id)
+        }
+
+        async fn add_thread_member(
+            &self,
+            thread_id: ChannelId,
+            user_id: UserId,
+        ) -> Result<(), Error> {
+            match self.private_threads.lock().unwrap().get_mut(&thread_id) {
+                Some(thread) => {
+                    thread.members.insert(user_id);
+                    Ok(())
+                }
+                None => Err(Error::Validation(format!("Unknown thread {thread_id}"))),
+            }
+        }
+
+        async fn create_channel(
+            &self,
+            name: String,
+            _category: Option<ChannelId>,
+            permissions: Vec<PermissionOverwrite>,
+        ) -> Result<ChannelId, Error> {
+            let channel_id = ChannelId::new(self.id());
+            self.channels
+                .lock()
+                .unwrap()
+                .push((channel_id, name, permissions));
+            Ok(channel_id)
+        }
+
+        async fn edit_channel(
+            &self,
+            channel_id: ChannelId,
+            _category: Option<ChannelId>,
+            permissions: Vec<PermissionOverwrite>,
+        ) -> Result<(), Error> {
+            self.edit_permissions(channel_id, |i| *i = permissions)
+        }
+
+        async fn delete_channel(&self, channel_id: ChannelId) -> Result<(), Error> {
+            self.edit_permissions(channel_id, |_| ())?;
+            self.channels
+                .lock()
+                .unwrap()
+                .retain(|(i, _, _)| *i != channel_id);
+            Ok(())
+        }
+
+        async fn set_permission(
+            &self,
+            channel_id: ChannelId,
+            permission: PermissionOverwrite,
+        ) -> Result<(), Error> {
+            self.edit_permissions(channel_id, |permissions| {
+                permissions.retain(|i| i.kind != permission.kind);
+                permissions.push(permission);
+            })
+        }
+
+        async fn remove_permission(
+            &self,
+            channel_id: ChannelId,
+            kind: PermissionOverwriteType,
+        ) -> Result<(), Error> {
+            self.edit_permissions(channel_id, |permissions| {
+                permissions.retain(|i| i.kind != kind)
+            })
+        }
+
+        async fn channel_names(&self) -> Result<HashMap<ChannelId, String>, Error> {
+            Ok(self
+                .channels
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(id, name, _)| (*id, name.clone()))
+                .collect())
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod message_log;
+pub mod transcript;
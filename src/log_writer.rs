@@ -0,0 +1,166 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use botc_discord_bot::message_log::{LogConfig, LogEvent, LogRecord, segment_path};
+use flate2::{Compression, write::GzEncoder};
+use tokio::sync::{mpsc, oneshot};
+
+/// Records that may queue up before whoever writes to the log has to wait for the disk
+const QUEUE_SIZE: usize = 1024;
+
+enum LogCommand {
+    Write(Box<LogRecord>),
+    Rotate,
+    Flush(oneshot::Sender<()>),
+}
+
+/// Writes the message log on a dedicated thread so a slow disk never stalls the event handler.
+/// Records are written in the order they are sent.
+pub struct LogWriter {
+    sender: mpsc::Sender<LogCommand>,
+}
+
+impl LogWriter {
+    pub fn spawn(config: LogConfig) -> std::io::Result<LogWriter> {
+        let mut segment = Segment::open(&config.path)?;
+        let (sender, mut receiver) = mpsc::channel(QUEUE_SIZE);
+
+        std::thread::Builder::new()
+            .name("message-log".to_owned())
+            .spawn(move || {
+                while let Some(command) = receiver.blocking_recv() {
+                    segment.handle(command, &config);
+                    // Write everything that queued up meanwhile before touching the disk again
+                    while let Ok(command) = receiver.try_recv() {
+                        segment.handle(command, &config);
+                    }
+                    if let Err(e) = segment.writer.flush() {
+                        println!("Failed to flush the message log: {e}");
+                    }
+                }
+            })?;
+
+        Ok(LogWriter { sender })
+    }
+
+    /// Queues a record, waiting for the disk only if the queue is full
+    pub async fn write(&self, event: LogEvent) {
+        let _ = self
+            .sender
+            .send(LogCommand::Write(Box::new(LogRecord::new(event))))
+            .await;
+    }
+
+    /// Closes the active segment and starts a new one
+    pub async fn rotate(&self) {
+        let _ = self.sender.send(LogCommand::Rotate).await;
+    }
+
+    /// Waits until everything written so far has reached the disk
+    pub async fn flush(&self) {
+        let (sender, receiver) = oneshot::channel();
+        if self.sender.send(LogCommand::Flush(sender)).await.is_ok() {
+            let _ = receiver.await;
+        }
+    }
+}
+
+struct Segment {
+    writer: BufWriter<File>,
+    size: u64,
+}
+
+impl Segment {
+    fn open(path: &Path) -> std::io::Result<Segment> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Segment {
+            writer: BufWriter::new(file),
+            size,
+        })
+    }
+
+    fn handle(&mut self, command: LogCommand, config: &LogConfig) {
+        let result = match command {
+            LogCommand::Write(record) => self.write(record, config),
+            LogCommand::Rotate => self.rotate(config),
+            LogCommand::Flush(done) => {
+                let result = self.writer.flush();
+                let _ = done.send(());
+                result
+            }
+        };
+
+        if let Err(e) = result {
+            println!("Failed to write to the message log: {e}");
+        }
+    }
+
+    fn write(&mut self, record: Box<LogRecord>, config: &LogConfig) -> std::io::Result<()> {
+        let mut line = vec![];
+        record.write_to(&mut line)?;
+
+        if config
+            .max_size
+            .is_some_and(|max_size| self.size > 0 && self.size + line.len() as u64 > max_size)
+        {
+            self.rotate(config)?;
+        }
+
+        self.writer.write_all(&line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self, config: &LogConfig) -> std::io::Result<()> {
+        if self.size == 0 {
+            return Ok(());
+        }
+
+        self.writer.flush()?;
+        // Segments are named by time, never overwrite one that was closed in the same millisecond
+        let mut rotated_at = SystemTime::now();
+        let closed = loop {
+            let closed = segment_path(&config.path, rotated_at);
+            if !closed.exists() && !compressed_path(&closed).exists() {
+                break closed;
+            }
+            rotated_at += Duration::from_millis(1);
+        };
+        std::fs::rename(&config.path, &closed)?;
+        *self = Segment::open(&config.path)?;
+
+        if config.compress {
+            std::thread::spawn(move || {
+                if let Err(e) = compress(&closed) {
+                    println!("Failed to compress {}: {e}", closed.display());
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn compressed_path(path: &Path) -> PathBuf {
+    let mut compressed_path = path.as_os_str().to_owned();
+    compressed_path.push(".gz");
+    compressed_path.into()
+}
+
+fn compress(path: &Path) -> std::io::Result<()> {
+    let compressed_path = compressed_path(path);
+    let mut partial_path = compressed_path.clone().into_os_string();
+    partial_path.push(".tmp");
+
+    // Readers ignore the partial file, and prefer the uncompressed segment while both exist
+    let mut encoder = GzEncoder::new(File::create(&partial_path)?, Compression::default());
+    std::io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    std::fs::rename(&partial_path, &compressed_path)?;
+    std::fs::remove_file(path)
+}
@@ -1,71 +1,103 @@
 mod commands;
+mod deadlines;
+mod engine;
+mod error;
+mod game;
+mod gateway;
+mod log_writer;
+mod modifiers;
+mod rules;
 mod state;
+#[cfg(test)]
+mod tests;
+mod town_square;
 use std::{
-    fmt::Display,
-    fs::{File, OpenOptions},
-    io::Write,
+    fs::OpenOptions,
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
 
 use crate::{
-    commands::{assign_player_to_cottage, set_accusation, set_number_of_players, start_vote},
-    state::State,
+    commands::{
+        add_reminder, assign_player_to_cottage, clock_hand_pings, close_cottages, end_day,
+        export_transcript, kill, move_player, open_signups, remove_reminder, reveal_votes,
+        seat_signups, set_accusation, set_character, set_number_of_players, shuffle_cottages,
+        start_day, start_vote, substitute, swap_cottages, tally, vacate_cottage, whisper,
+        whisper_settings,
+    },
+    error::{Error, on_error},
+    game::{Button, Game, MAX_STATEMENT_LENGTH, parse_statement_modal, statement_modal_id},
+    gateway::SerenityGateway,
+    state::{MessageStyle, State, Statement},
 };
+use botc_discord_bot::message_log::{LogConfig, LogEvent};
 use commands::{raise_hand, set_defense, vote};
-use poise::{
-    FrameworkError,
-    serenity_prelude::{
-        self as serenity, ComponentInteractionDataKind, CreateInteractionResponseMessage,
-        EditMessage, GuildId, Interaction, RoleId, futures::lock::Mutex,
-    },
+use deadlines::DeadlineConfig;
+use log_writer::LogWriter;
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, ComponentInteractionDataKind, CreateInteractionResponse,
+    CreateInteractionResponseMessage, GuildId, Interaction, MessageId, RoleId,
 };
 use serde::Deserialize;
-use state::format_vote;
 use tokio::sync::RwLock;
+use town_square::TownSquareConfig;
 
-#[derive(Debug)]
-enum Error {
-    Serenity(#[allow(unused)] poise::serenity_prelude::Error),
-    Silent,
-}
-
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self:?}")
-    }
-}
-
-impl From<poise::serenity_prelude::Error> for Error {
-    fn from(value: poise::serenity_prelude::Error) -> Self {
-        Error::Serenity(value)
-    }
-}
-
-type DiscordState = (Config, RwLock<State>, Mutex<File>);
+type DiscordState = (Config, Arc<RwLock<State>>, LogWriter);
 type Context<'a> = poise::Context<'a, DiscordState, Error>;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 struct Config {
     token: String,
     guild_id: GuildId,
     storyteller_role: RoleId,
     dead_role: RoleId,
     ghost_vote_available_role: RoleId,
+    #[serde(default)]
+    log: LogConfig,
+    /// Channel to open whisper threads in, defaults to wherever `/whisper` is used
+    whisper_channel: Option<ChannelId>,
+    /// Category to create cottage channels in when none is given
+    cottage_category: Option<ChannelId>,
+    /// Category cottage channels are moved to when archived
+    cottage_archive_category: Option<ChannelId>,
+    #[serde(default)]
+    message_style: MessageStyle,
+    #[serde(default)]
+    town_square: TownSquareConfig,
+    #[serde(default)]
+    deadlines: DeadlineConfig,
+}
+
+fn load_config() -> Result<Config, Error> {
+    let reader = OpenOptions::new()
+        .read(true)
+        .open("config.yaml")
+        .map_err(|e| Error::Config(e.to_string()))?;
+    serde_yml::from_reader(reader).map_err(|e| Error::Config(e.to_string()))
 }
 
-fn get_initial_state() -> state::State {
-    let reader = match OpenOptions::new().read(true).open("state.yaml") {
-        Ok(e) => e,
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return State::default(),
-        k => k.unwrap(),
+fn get_initial_state() -> Result<State, Error> {
+    let mut state: State = match OpenOptions::new().read(true).open("state.yaml") {
+        Ok(reader) => serde_yml::from_reader(reader)
+            .map_err(|e| Error::Persistence(format!("Couldn't read state.yaml: {e}")))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => State::default(),
+        Err(e) => return Err(Error::Persistence(format!("Couldn't open state.yaml: {e}"))),
     };
-    serde_yml::from_reader(reader).unwrap()
+    state.save_path = Some("state.yaml".into());
+    Ok(state)
 }
 
 #[tokio::main]
 async fn main() {
-    let config: Config =
-        serde_yml::from_reader(OpenOptions::new().read(true).open("config.yaml").unwrap()).unwrap();
-    let state = get_initial_state();
+    if let Err(e) = run().await {
+        println!("{e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), Error> {
+    let config = load_config()?;
+    let state = get_initial_state()?;
 
     let intents =
         serenity::GatewayIntents::non_privileged() | serenity::GatewayIntents::MESSAGE_CONTENT;
@@ -78,29 +110,31 @@ async fn main() {
                 let commands =
                     poise::builtins::create_application_commands(&framework.options().commands);
 
-                config.guild_id.set_commands(ctx, commands).await.unwrap();
-                Ok((
-                    config,
-                    RwLock::new(state),
-                    Mutex::new(
-                        OpenOptions::new()
-                            .append(true)
-                            .create(true)
-                            .open("message_log.jsonl")
-                            .unwrap(),
-                    ),
-                ))
+                config.guild_id.set_commands(ctx, commands).await?;
+                let log_writer = LogWriter::spawn(config.log.clone()).map_err(|e| {
+                    Error::Persistence(format!("Couldn't open the message log: {e}"))
+                })?;
+
+                // A vote might have been running when the bot went down
+                let state = Arc::new(RwLock::new(state));
+                let game = Game {
+                    gateway: SerenityGateway {
+                        ctx,
+                        guild: config.guild_id,
+                    },
+                    config: &config,
+                    state: &state,
+                };
+                if let Err(e) = game.restore_vote().await {
+                    println!("Failed to restore the vote message: {e}");
+                }
+                tokio::spawn(watch_deadlines(ctx.clone(), config.clone(), state.clone()));
+
+                Ok((config, state, log_writer))
             })
         })
         .options(poise::FrameworkOptions {
-            on_error: |err| {
-                Box::pin(async move {
-                    match err {
-                        FrameworkError::CommandCheckFailed { .. } => (),
-                        err => poise::builtins::on_error(err).await.unwrap(),
-                    }
-                })
-            },
+            on_error: |err| Box::pin(on_error(err)),
             event_handler: |ctx, event, framework, state| {
                 Box::pin(async move { event_handler(ctx, event, framework, state).await })
             },
@@ -113,6 +147,26 @@ async fn main() {
                 set_defense(),
                 raise_hand(),
                 vote(),
+                start_day(),
+                end_day(),
+                set_character(),
+                kill(),
+                add_reminder(),
+                remove_reminder(),
+                tally(),
+                reveal_votes(),
+                export_transcript(),
+                whisper(),
+                whisper_settings(),
+                close_cottages(),
+                swap_cottages(),
+                vacate_cottage(),
+                shuffle_cottages(),
+                move_player(),
+                open_signups(),
+                seat_signups(),
+                substitute(),
+                clock_hand_pings(),
             ],
             prefix_options: poise::PrefixFrameworkOptions {
                 prefix: Some("~".into()),
@@ -122,11 +176,13 @@ async fn main() {
         })
         .build();
 
-    let client = serenity::ClientBuilder::new(token, intents)
+    serenity::ClientBuilder::new(token, intents)
         .framework(framework)
-        .await;
+        .await?
+        .start()
+        .await?;
 
-    client.unwrap().start().await.unwrap();
+    Ok(())
 }
 
 async fn event_handler<'a>(
@@ -137,204 +193,369 @@ async fn event_handler<'a>(
 ) -> Result<(), Error> {
     match event {
         serenity::FullEvent::Message { new_message } => {
-            let mut value = state.2.lock().await;
-
-            let mut message = serde_json::to_vec(&(
-                "new_message",
-                std::time::SystemTime::now(),
-                new_message.id,
-                &new_message.content,
-                &new_message.author,
-                new_message.channel_id,
-                new_message.thread.as_ref().map(|i| (i.id, &i.name)),
-                new_message
-                    .attachments
-                    .iter()
-                    .map(|i| &i.url)
-                    .collect::<Vec<_>>(),
-            ))
-            .unwrap();
-            message.push(10);
-            value.write_all(&message).unwrap();
+            state
+                .2
+                .write(LogEvent::NewMessage {
+                    message_id: new_message.id,
+                    content: new_message.content.clone(),
+                    author: new_message.author.clone(),
+                    channel_id: new_message.channel_id,
+                    thread: new_message.thread.as_ref().map(|i| (i.id, i.name.clone())),
+                    attachments: new_message
+                        .attachments
+                        .iter()
+                        .map(|i| i.url.clone())
+                        .collect(),
+                })
+                .await;
+
+            if !new_message.author.bot {
+                let game = game(ctx, state);
+                game.whisper_message(new_message.channel_id, new_message.author.id)
+                    .await?;
+                game.thread_message(
+                    new_message.channel_id,
+                    new_message.author.id,
+                    &new_message.content,
+                )
+                .await?;
+            }
         }
         serenity::FullEvent::MessageUpdate {
             old_if_available: _,
             new: _,
             event: ev,
         } => {
-            let mut value = state.2.lock().await;
-
-            let mut message = serde_json::to_vec(&(
-                "message_edit",
-                std::time::SystemTime::now(),
-                ev.id,
-                ev.id,
-                &ev.content,
-                &ev.author,
-                &ev.channel_id,
-                &ev.reactions,
-            ))
-            .unwrap();
-            message.push(10);
-            value.write_all(&message).unwrap();
+            state
+                .2
+                .write(LogEvent::MessageEdit {
+                    message_id: ev.id,
+                    content: ev.content.clone(),
+                    author: ev.author.clone(),
+                    channel_id: ev.channel_id,
+                    reactions: ev.reactions.clone(),
+                })
+                .await;
         }
         serenity::FullEvent::MessageDelete {
             channel_id,
             deleted_message_id,
             guild_id,
         } => {
-            let mut value = state.2.lock().await;
-
-            let mut message = serde_json::to_vec(&(
-                "message_delete",
-                std::time::SystemTime::now(),
-                channel_id,
-                deleted_message_id,
-                guild_id,
-            ))
-            .unwrap();
-
-            message.push(10);
-            value.write_all(&message).unwrap();
+            state
+                .2
+                .write(LogEvent::MessageDelete {
+                    channel_id: *channel_id,
+                    message_id: *deleted_message_id,
+                    guild_id: *guild_id,
+                })
+                .await;
         }
         serenity::FullEvent::ReactionAdd { add_reaction } => {
-            let mut value = state.2.lock().await;
-
-            let mut message = serde_json::to_vec(&(
-                "reaction_add",
-                std::time::SystemTime::now(),
-                add_reaction.channel_id,
-                &add_reaction.emoji,
-                add_reaction.message_id,
-                add_reaction.user_id,
-            ))
-            .unwrap();
-
-            message.push(10);
-            value.write_all(&message).unwrap();
+            state
+                .2
+                .write(LogEvent::ReactionAdd {
+                    channel_id: add_reaction.channel_id,
+                    emoji: add_reaction.emoji.clone(),
+                    message_id: add_reaction.message_id,
+                    user_id: add_reaction.user_id,
+                })
+                .await;
         }
         serenity::FullEvent::ReactionRemove { removed_reaction } => {
-            let mut value = state.2.lock().await;
-
-            let mut message = serde_json::to_vec(&(
-                "reaction_remove",
-                std::time::SystemTime::now(),
-                removed_reaction.channel_id,
-                &removed_reaction.emoji,
-                removed_reaction.message_id,
-                removed_reaction.user_id,
-            ))
-            .unwrap();
-
-            message.push(10);
-            value.write_all(&message).unwrap();
+            state
+                .2
+                .write(LogEvent::ReactionRemove {
+                    channel_id: removed_reaction.channel_id,
+                    emoji: removed_reaction.emoji.clone(),
+                    message_id: removed_reaction.message_id,
+                    user_id: removed_reaction.user_id,
+                })
+                .await;
         }
         serenity::FullEvent::ThreadCreate { thread } => {
-            let mut value = state.2.lock().await;
-
-            let mut message = serde_json::to_vec(&(
-                "thread_create",
-                std::time::SystemTime::now(),
-                thread.id,
-                &thread.name,
-                &thread.thread_metadata,
-            ))
-            .unwrap();
-
-            message.push(10);
-            value.write_all(&message).unwrap();
+            state
+                .2
+                .write(LogEvent::ThreadCreate {
+                    thread_id: thread.id,
+                    name: thread.name.clone(),
+                    thread_metadata: thread.thread_metadata,
+                })
+                .await;
         }
         serenity::FullEvent::ThreadMemberUpdate { thread_member } => {
-            let mut value = state.2.lock().await;
-
-            let mut message = serde_json::to_vec(&(
-                "thread_member_update",
-                std::time::SystemTime::now(),
-                thread_member.id,
-                thread_member.user_id,
-                thread_member.inner.join_timestamp,
-            ))
-            .unwrap();
-
-            message.push(10);
-            value.write_all(&message).unwrap();
+            state
+                .2
+                .write(LogEvent::ThreadMemberUpdate {
+                    thread_id: thread_member.id,
+                    user_id: thread_member.user_id,
+                    join_timestamp: thread_member.inner.join_timestamp,
+                })
+                .await;
         }
         serenity::FullEvent::InteractionCreate {
             interaction: Interaction::Component(component_interaction),
         } => {
             if let ComponentInteractionDataKind::Button = component_interaction.data.kind {
-                let up = component_interaction.data.custom_id == "hand_up_button";
-                println!("Received a hand {up} up response");
-
-                let mut ok = false;
-                {
-                    let State {
-                        players,
-                        current_vote,
-                        number_of_players,
-                        ..
-                    } = &mut *state.1.write().await;
-
-                    if let Some(vote) = current_vote {
-                        let v = vote
-                            .vote_state
-                            .entry(component_interaction.user.id)
-                            .or_insert(state::VoteState::None);
-
-                        match v {
-                            state::VoteState::Yes | state::VoteState::No => (),
-                            e => {
-                                *e = if up {
-                                    state::VoteState::HandRaised
-                                } else {
-                                    state::VoteState::HandLowered
-                                }
-                            }
-                        }
-
-                        let mut message = ctx
-                            .http
-                            .get_message(vote.channel_id, vote.message_id)
-                            .await?;
-                        message
-                            .edit(
-                                ctx,
-                                EditMessage::new().content(format_vote(
-                                    players,
-                                    vote,
-                                    *number_of_players,
-                                )),
-                            )
-                            .await?;
-
-                        ok = true;
-                        component_interaction
-                            .create_response(
-                                ctx,
-                                serenity::CreateInteractionResponse::UpdateMessage(
-                                    CreateInteractionResponseMessage::new(),
-                                ),
-                            )
-                            .await?;
+                let up = match Button::parse(&component_interaction.data.custom_id) {
+                    Some(Button::Hand { raised }) => raised,
+                    Some(Button::WriteStatement(statement)) => {
+                        return statement_modal(ctx, component_interaction, state, statement).await;
                     }
-                }
-
-                state.1.read().await.save();
-
-                if !ok {
-                    component_interaction
-                        .create_response(
+                    Some(Button::ClockHand { vote, yes }) => {
+                        return clock_hand_button(ctx, component_interaction, state, vote, yes)
+                            .await;
+                    }
+                    Some(Button::ResolveStatement {
+                        vote,
+                        statement,
+                        confirm,
+                    }) => {
+                        return statement_button(
                             ctx,
-                            serenity::CreateInteractionResponse::UpdateMessage(
-                                CreateInteractionResponseMessage::new(),
-                            ),
+                            component_interaction,
+                            state,
+                            vote,
+                            statement,
+                            confirm,
                         )
-                        .await?;
-                }
+                        .await;
+                    }
+                    Some(Button::SignUp { join }) => {
+                        return signup_button(ctx, component_interaction, state, join).await;
+                    }
+                    // Other buttons are handled by collectors in their commands
+                    None => return Ok(()),
+                };
+                println!("Received a hand {up} up response");
+
+                let game = game(ctx, state);
+                // Hands can't change once the clock hand has passed, tell the player why
+                let response = match game.set_hand(component_interaction.user.id, up).await {
+                    Ok(_) => CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new(),
+                    ),
+                    Err(e @ Error::Game(_)) => CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .ephemeral(true)
+                            .content(e.user_message()),
+                    ),
+                    Err(e) => return Err(e),
+                };
+                component_interaction.create_response(ctx, response).await?;
             }
         }
+        serenity::FullEvent::InteractionCreate {
+            interaction: Interaction::Modal(modal_interaction),
+        } => {
+            let Some(statement) = parse_statement_modal(&modal_interaction.data.custom_id) else {
+                return Ok(());
+            };
+            let text = modal_interaction
+                .data
+                .components
+                .iter()
+                .flat_map(|i| &i.components)
+                .find_map(|i| match i {
+                    serenity::ActionRowComponent::InputText(input) => input.value.clone(),
+                    _ => None,
+                })
+                .unwrap_or_default();
+
+            let result = game(ctx, state)
+                .set_statement(
+                    statement,
+                    modal_interaction.user.id,
+                    is_storyteller(state, modal_interaction.member.as_ref()),
+                    text.trim().to_owned(),
+                )
+                .await;
+            let response = match result {
+                Ok(()) => CreateInteractionResponse::Acknowledge,
+                Err(e) => CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(e.user_message()),
+                ),
+            };
+            modal_interaction.create_response(ctx, response).await?;
+        }
         _ => (),
     }
 
     Ok(())
 }
+
+/// A player voting from the ping they got when the clock hand reached them
+async fn clock_hand_button(
+    ctx: &serenity::Context,
+    component_interaction: &serenity::ComponentInteraction,
+    state: &DiscordState,
+    vote: MessageId,
+    yes: bool,
+) -> Result<(), Error> {
+    let response = match game(ctx, state)
+        .cast_own_vote(vote, component_interaction.user.id, yes)
+        .await
+    {
+        Ok(_) => CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(if yes { "You voted yes" } else { "You voted no" })
+                .components(vec![]),
+        ),
+        Err(e) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(e.user_message()),
+        ),
+    };
+    component_interaction.create_response(ctx, response).await?;
+
+    Ok(())
+}
+
+fn is_storyteller(state: &DiscordState, member: Option<&serenity::Member>) -> bool {
+    member.is_some_and(|i| i.roles.contains(&state.0.storyteller_role))
+}
+
+/// Asks the nominator or nominee for their statement, starting from what's already there
+async fn statement_modal(
+    ctx: &serenity::Context,
+    component_interaction: &serenity::ComponentInteraction,
+    state: &DiscordState,
+    statement: Statement,
+) -> Result<(), Error> {
+    let current = game(ctx, state)
+        .statement(
+            statement,
+            component_interaction.user.id,
+            is_storyteller(state, component_interaction.member.as_ref()),
+        )
+        .await;
+    let response = match current {
+        Ok(current) => {
+            let title = match statement {
+                Statement::Accusation => "Accusation",
+                Statement::Defense => "Defense",
+            };
+            let mut input = serenity::CreateInputText::new(
+                serenity::InputTextStyle::Paragraph,
+                title,
+                "statement",
+            )
+            .max_length(MAX_STATEMENT_LENGTH as u16)
+            .required(false);
+            if !current.is_empty() {
+                input = input.value(current);
+            }
+            CreateInteractionResponse::Modal(
+                serenity::CreateModal::new(statement_modal_id(statement), title)
+                    .components(vec![serenity::CreateActionRow::InputText(input)]),
+            )
+        }
+        Err(e) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(e.user_message()),
+        ),
+    };
+    component_interaction.create_response(ctx, response).await?;
+
+    Ok(())
+}
+
+/// Checks the deadlines of the active vote every so often, for as long as the bot runs
+async fn watch_deadlines(ctx: serenity::Context, config: Config, state: Arc<RwLock<State>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(30));
+    loop {
+        interval.tick().await;
+        let game = Game {
+            gateway: SerenityGateway {
+                ctx: &ctx,
+                guild: config.guild_id,
+            },
+            config: &config,
+            state: &state,
+        };
+        if let Err(e) = game.check_deadlines(SystemTime::now()).await {
+            println!("Failed to check the vote deadlines: {e}");
+        }
+    }
+}
+
+fn game<'a>(ctx: &'a serenity::Context, state: &'a DiscordState) -> Game<'a, SerenityGateway<'a>> {
+    Game {
+        gateway: SerenityGateway {
+            ctx,
+            guild: state.0.guild_id,
+        },
+        config: &state.0,
+        state: &state.1,
+    }
+}
+
+/// A storyteller accepting or dismissing a statement posted in a vote thread
+async fn statement_button(
+    ctx: &serenity::Context,
+    component_interaction: &serenity::ComponentInteraction,
+    state: &DiscordState,
+    vote: MessageId,
+    statement: Statement,
+    confirm: bool,
+) -> Result<(), Error> {
+    let response = if !is_storyteller(state, component_interaction.member.as_ref()) {
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content("Only storytellers can do this"),
+        )
+    } else {
+        let content = match game(ctx, state)
+            .resolve_statement(vote, statement, confirm)
+            .await
+        {
+            Ok(()) if !confirm => "Dismissed".to_owned(),
+            Ok(()) => match statement {
+                Statement::Accusation => "Accusation set".to_owned(),
+                Statement::Defense => "Defense set".to_owned(),
+            },
+            Err(e) => e.user_message(),
+        };
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .components(vec![]),
+        )
+    };
+    component_interaction.create_response(ctx, response).await?;
+
+    Ok(())
+}
+
+async fn signup_button(
+    ctx: &serenity::Context,
+    component_interaction: &serenity::ComponentInteraction,
+    state: &DiscordState,
+    join: bool,
+) -> Result<(), Error> {
+    let user_id = component_interaction.user.id;
+    let game = game(ctx, state);
+    // The sign-up message is edited to match the queue, nothing else to update then
+    let result = if join {
+        game.join_signups(user_id).await
+    } else {
+        game.leave_signups(user_id).await
+    };
+    let response = match result {
+        Ok(()) => CreateInteractionResponse::Acknowledge,
+        Err(e @ Error::Validation(_)) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(e.user_message()),
+        ),
+        Err(e) => return Err(e),
+    };
+    component_interaction.create_response(ctx, response).await?;
+
+    Ok(())
+}
@@ -0,0 +1,383 @@
+//! Record types for `message_log.jsonl`, shared between the bot writing the log and tools reading
+//! it back. Older versions of the bot wrote anonymous JSON arrays, those can still be read with
+//! [`parse_line`].
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use flate2::read::GzDecoder;
+
+use poise::serenity_prelude::{
+    ChannelId, GuildId, MessageId, MessageReaction, ReactionType, ThreadMetadata, Timestamp, User,
+    UserId,
+};
+use serde::{Deserialize, Serialize};
+
+/// Where the bot writes the log and when it starts a new segment, the `log` section of
+/// `config.yaml`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LogConfig {
+    pub path: PathBuf,
+    /// Start a new segment once the active one grows past this many bytes
+    pub max_size: Option<u64>,
+    /// Start a new segment whenever a new game starts
+    pub rotate_per_game: bool,
+    /// Gzip segments once they are closed
+    pub compress: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            path: PathBuf::from("message_log.jsonl"),
+            max_size: None,
+            rotate_per_game: false,
+            compress: false,
+        }
+    }
+}
+
+/// Bump this whenever a change to [`LogEvent`] is not backwards compatible
+pub const LOG_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LogRecord {
+    /// Every record is written with a version, legacy array lines are read back as version 0 by
+    /// [`parse_line`]
+    pub version: u32,
+    pub time: SystemTime,
+    #[serde(flatten)]
+    pub event: LogEvent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogEvent {
+    NewMessage {
+        message_id: MessageId,
+        content: String,
+        author: User,
+        channel_id: ChannelId,
+        thread: Option<(ChannelId, String)>,
+        attachments: Vec<String>,
+    },
+    MessageEdit {
+        message_id: MessageId,
+        content: Option<String>,
+        author: Option<User>,
+        channel_id: ChannelId,
+        reactions: Option<Vec<MessageReaction>>,
+    },
+    MessageDelete {
+        channel_id: ChannelId,
+        message_id: MessageId,
+        guild_id: Option<GuildId>,
+    },
+    ReactionAdd {
+        channel_id: ChannelId,
+        emoji: ReactionType,
+        message_id: MessageId,
+        user_id: Option<UserId>,
+    },
+    ReactionRemove {
+        channel_id: ChannelId,
+        emoji: ReactionType,
+        message_id: MessageId,
+        user_id: Option<UserId>,
+    },
+    ThreadCreate {
+        thread_id: ChannelId,
+        name: String,
+        thread_metadata: Option<ThreadMetadata>,
+    },
+    ThreadMemberUpdate {
+        thread_id: ChannelId,
+        user_id: UserId,
+        join_timestamp: Timestamp,
+    },
+    /// A storyteller replaced the player in a cottage
+    Substitution {
+        cottage: u32,
+        old: UserId,
+        new: UserId,
+    },
+    /// Who sat in which cottage channel, logged whenever the seating changes
+    Seating {
+        cottages: Vec<(u32, UserId, ChannelId)>,
+    },
+}
+
+impl LogRecord {
+    pub fn new(event: LogEvent) -> LogRecord {
+        LogRecord {
+            version: LOG_VERSION,
+            time: SystemTime::now(),
+            event,
+        }
+    }
+
+    /// Writes the record as a single line of JSON
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        writer.write_all(&line)
+    }
+}
+
+/// Parses a single line of the log, in either the current or the legacy tuple format
+pub fn parse_line(line: &str) -> serde_json::Result<LogRecord> {
+    let value: serde_json::Value = serde_json::from_str(line)?;
+    if value.is_array() {
+        parse_legacy(value)
+    } else {
+        serde_json::from_value(value)
+    }
+}
+
+/// Reads every record from a log, skipping blank lines
+pub fn read_log(reader: impl BufRead) -> impl Iterator<Item = std::io::Result<LogRecord>> {
+    reader.lines().filter_map(|line| match line {
+        Ok(line) if line.trim().is_empty() => None,
+        Ok(line) => Some(parse_line(&line).map_err(std::io::Error::from)),
+        Err(e) => Some(Err(e)),
+    })
+}
+
+/// Name of a closed segment of the log at `path`, eg. `message_log.1700000000000.jsonl`
+pub fn segment_path(path: &Path, rotated_at: SystemTime) -> PathBuf {
+    let millis = rotated_at
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}.{millis}.{extension}"))
+}
+
+/// Every segment of the log at `path` in the order they were written, ending with the active one
+pub fn log_segments(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut segments: Vec<(u128, PathBuf)> = vec![];
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".tmp") {
+            continue;
+        }
+        let Some(rotated_at) = name
+            .strip_prefix(&*stem)
+            .and_then(|i| i.strip_prefix('.'))
+            .and_then(|i| i.split('.').next())
+            .and_then(|i| i.parse().ok())
+        else {
+            continue;
+        };
+        segments.push((rotated_at, entry.path()));
+    }
+    // A segment that is being compressed exists twice for a moment
+    segments.sort();
+    segments.dedup_by_key(|(rotated_at, _)| *rotated_at);
+
+    let mut segments: Vec<_> = segments.into_iter().map(|(_, path)| path).collect();
+    if path.exists() {
+        segments.push(path.to_owned());
+    }
+    Ok(segments)
+}
+
+/// Reads every record from every segment of the log at `path`, including compressed segments
+pub fn read_all_segments(
+    path: &Path,
+) -> std::io::Result<impl Iterator<Item = std::io::Result<LogRecord>>> {
+    let mut readers: Vec<Box<dyn BufRead>> = vec![];
+    for mut segment in log_segments(path)? {
+        let file = match File::open(&segment) {
+            // The segment got compressed since we listed the directory
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let mut compressed = segment.into_os_string();
+                compressed.push(".gz");
+                segment = compressed.into();
+                File::open(&segment)?
+            }
+            file => file?,
+        };
+        readers.push(if segment.extension().is_some_and(|i| i == "gz") {
+            Box::new(BufReader::new(GzDecoder::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        });
+    }
+    Ok(readers.into_iter().flat_map(read_log))
+}
+
+type LegacyNewMessage = (
+    String,
+    SystemTime,
+    MessageId,
+    String,
+    User,
+    ChannelId,
+    Option<(ChannelId, String)>,
+    Vec<String>,
+);
+
+/// The legacy format wrote the message ID twice
+type LegacyMessageEdit = (
+    String,
+    SystemTime,
+    MessageId,
+    MessageId,
+    Option<String>,
+    Option<User>,
+    ChannelId,
+    Option<Vec<MessageReaction>>,
+);
+
+fn parse_legacy(value: serde_json::Value) -> serde_json::Result<LogRecord> {
+    let kind = value
+        .get(0)
+        .and_then(|i| i.as_str())
+        .unwrap_or_default()
+        .to_owned();
+
+    let (time, event) = match kind.as_str() {
+        "new_message" => {
+            let legacy: LegacyNewMessage = serde_json::from_value(value)?;
+            let (_, time, message_id, content, author, channel_id, thread, attachments) = legacy;
+            (
+                time,
+                LogEvent::NewMessage {
+                    message_id,
+                    content,
+                    author,
+                    channel_id,
+                    thread,
+                    attachments,
+                },
+            )
+        }
+        "message_edit" => {
+            let legacy: LegacyMessageEdit = serde_json::from_value(value)?;
+            let (_, time, message_id, _, content, author, channel_id, reactions) = legacy;
+            (
+                time,
+                LogEvent::MessageEdit {
+                    message_id,
+                    content,
+                    author,
+                    channel_id,
+                    reactions,
+                },
+            )
+        }
+        "message_delete" => {
+            let (_, time, channel_id, message_id, guild_id): (
+                String,
+                SystemTime,
+                ChannelId,
+                MessageId,
+                Option<GuildId>,
+            ) = serde_json::from_value(value)?;
+            (
+                time,
+                LogEvent::MessageDelete {
+                    channel_id,
+                    message_id,
+                    guild_id,
+                },
+            )
+        }
+        "reaction_add" | "reaction_remove" => {
+            let (_, time, channel_id, emoji, message_id, user_id): (
+                String,
+                SystemTime,
+                ChannelId,
+                ReactionType,
+                MessageId,
+                Option<UserId>,
+            ) = serde_json::from_value(value)?;
+            (
+                time,
+                if kind == "reaction_add" {
+                    LogEvent::ReactionAdd {
+                        channel_id,
+                        emoji,
+                        message_id,
+                        user_id,
+                    }
+                } else {
+                    LogEvent::ReactionRemove {
+                        channel_id,
+                        emoji,
+                        message_id,
+                        user_id,
+                    }
+                },
+            )
+        }
+        "thread_create" => {
+            let (_, time, thread_id, name, thread_metadata): (
+                String,
+                SystemTime,
+                ChannelId,
+                String,
+                Option<ThreadMetadata>,
+            ) = serde_json::from_value(value)?;
+            (
+                time,
+                LogEvent::ThreadCreate {
+                    thread_id,
+                    name,
+                    thread_metadata,
+                },
+            )
+        }
+        "thread_member_update" => {
+            let (_, time, thread_id, user_id, join_timestamp): (
+                String,
+                SystemTime,
+                ChannelId,
+                UserId,
+                Timestamp,
+            ) = serde_json::from_value(value)?;
+            (
+                time,
+                LogEvent::ThreadMemberUpdate {
+                    thread_id,
+                    user_id,
+                    join_timestamp,
+                },
+            )
+        }
+        other => {
+            return Err(serde::de::Error::unknown_variant(
+                other,
+                &[
+                    "new_message",
+                    "message_edit",
+                    "message_delete",
+                    "reaction_add",
+                    "reaction_remove",
+                    "thread_create",
+                    "thread_member_update",
+                ],
+            ));
+        }
+    };
+
+    Ok(LogRecord {
+        version: 0,
+        time,
+        event,
+    })
+}
@@ -0,0 +1,163 @@
+use std::{collections::HashMap, fmt::Display};
+
+use poise::serenity_prelude::UserId;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{Character, FormatMention, PlayerMap, Vote, VoteState};
+
+/// Reminder tokens the storyteller places that change how votes are counted
+#[derive(Serialize, Deserialize, poise::ChoiceParameter, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReminderToken {
+    #[name = "Butler: Master"]
+    ButlerMaster,
+    #[name = "Banshee: Has Ability"]
+    BansheeHasAbility,
+    #[name = "Bureaucrat: 3 Votes"]
+    BureaucratThreeVotes,
+    #[name = "Thief: Negative Vote"]
+    ThiefNegativeVote,
+}
+
+pub type ReminderMap = HashMap<UserId, Vec<ReminderToken>>;
+
+pub struct VoteModifiers<'a> {
+    pub characters: &'a HashMap<UserId, Character>,
+    pub reminders: &'a ReminderMap,
+}
+
+/// The result of counting a vote with every character ability applied
+pub struct Tally {
+    pub votes: u32,
+    pub notes: Vec<String>,
+}
+
+impl Display for Tally {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "**Tally:** {}", self.votes)?;
+        for note in &self.notes {
+            writeln!(f, "- {note}")?;
+        }
+        Ok(())
+    }
+}
+
+impl VoteModifiers<'_> {
+    fn is(&self, user_id: UserId, name: &str) -> bool {
+        self.characters.get(&user_id).is_some_and(|i| i.is(name))
+    }
+
+    fn has_reminder(&self, user_id: UserId, token: ReminderToken) -> bool {
+        self.reminders
+            .get(&user_id)
+            .is_some_and(|i| i.contains(&token))
+    }
+
+    /// While the Voudon lives only they and the dead vote, and the dead keep their ghost votes
+    pub fn voudon_in_play(&self, vote: &Vote) -> bool {
+        self.characters
+            .iter()
+            .any(|(user_id, character)| character.is("Voudon") && vote.is_alive(*user_id))
+    }
+
+    pub fn is_butler(&self, user_id: UserId) -> bool {
+        self.is(user_id, "Butler")
+    }
+
+    /// Whoever the storyteller marked as the Butler's master
+    pub fn master(&self) -> Option<UserId> {
+        self.reminders
+            .iter()
+            .find(|(_, tokens)| tokens.contains(&ReminderToken::ButlerMaster))
+            .map(|(user_id, _)| *user_id)
+    }
+
+    /// Votes needed to put the nominee on the block. The Voudon does away with the majority, any
+    /// vote at all is enough.
+    pub fn threshold(&self, vote: &Vote, players: &PlayerMap) -> u32 {
+        if self.voudon_in_play(vote) {
+            1
+        } else {
+            vote.threshold(players)
+        }
+    }
+
+    pub fn tally(&self, vote: &Vote) -> Tally {
+        let voudon_in_play = self.voudon_in_play(vote);
+        let master = self.master();
+
+        let mut voters: Vec<_> = vote
+            .vote_state
+            .iter()
+            .filter(|(_, vote_state)| matches!(vote_state, VoteState::Yes))
+            .map(|(user_id, _)| *user_id)
+            .collect();
+        voters.sort();
+
+        let mut votes = 0i32;
+        let mut notes = vec![];
+        for voter in voters {
+            if !voudon_in_play && !vote.may_vote(voter) {
+                notes.push(format!(
+                    "{} is dead and has no ghost vote left, their vote doesn't count",
+                    FormatMention(voter)
+                ));
+                continue;
+            }
+
+            if voudon_in_play && vote.is_alive(voter) && !self.is(voter, "Voudon") {
+                notes.push(format!(
+                    "{} is alive while the Voudon is in play, their vote doesn't count",
+                    FormatMention(voter)
+                ));
+                continue;
+            }
+
+            // What counts is the master's hand when the clock hand reached the Butler
+            let master_voting = |master| {
+                vote.butler_master_voting
+                    .unwrap_or_else(|| matches!(vote.vote_state.get(&master), Some(VoteState::Yes)))
+            };
+            if self.is_butler(voter)
+                && let Some(master) = master
+                && !master_voting(master)
+            {
+                notes.push(format!(
+                    "{} is the Butler and their master {} did not vote, their vote doesn't count",
+                    FormatMention(voter),
+                    FormatMention(master)
+                ));
+                continue;
+            }
+
+            let mut weight = 1;
+            if self.has_reminder(voter, ReminderToken::BansheeHasAbility) {
+                weight *= 2;
+                notes.push(format!(
+                    "{} is the Banshee and votes twice",
+                    FormatMention(voter)
+                ));
+            }
+            if self.has_reminder(voter, ReminderToken::BureaucratThreeVotes) {
+                weight *= 3;
+                notes.push(format!(
+                    "{}'s vote counts three times (Bureaucrat)",
+                    FormatMention(voter)
+                ));
+            }
+            if self.has_reminder(voter, ReminderToken::ThiefNegativeVote) {
+                weight = -weight;
+                notes.push(format!(
+                    "{}'s vote counts negatively (Thief)",
+                    FormatMention(voter)
+                ));
+            }
+
+            votes += weight;
+        }
+
+        Tally {
+            votes: votes.max(0) as u32,
+            notes,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt::Display;
+
+use poise::serenity_prelude::UserId;
+
+use crate::state::{CharacterType, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Team {
+    Good,
+    Evil,
+}
+
+impl Display for Team {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Team::Good => write!(f, "Good"),
+            Team::Evil => write!(f, "Evil"),
+        }
+    }
+}
+
+/// Something that happened to the town that could end the game
+#[derive(Debug, Clone, Copy)]
+pub enum LifeEvent {
+    Died,
+    Executed(UserId),
+    NoExecution,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOver {
+    DemonDied,
+    TwoPlayersLeft,
+    SaintExecuted,
+    MayorWin,
+}
+
+impl GameOver {
+    pub fn winner(self) -> Team {
+        match self {
+            GameOver::DemonDied | GameOver::MayorWin => Team::Good,
+            GameOver::TwoPlayersLeft | GameOver::SaintExecuted => Team::Evil,
+        }
+    }
+}
+
+impl Display for GameOver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            GameOver::DemonDied => "The Demon is dead",
+            GameOver::TwoPlayersLeft => "Only two players remain with the Demon alive",
+            GameOver::SaintExecuted => "The Saint was executed",
+            GameOver::MayorWin => {
+                "Three players remain and nobody was executed with the Mayor alive"
+            }
+        };
+        write!(f, "{reason}, {} wins!", self.winner())
+    }
+}
+
+/// Evaluates the standard end conditions after `event`. `alive` must already reflect the event,
+/// so an executed or killed player should no longer be in it.
+pub fn check_game_over(state: &State, alive: &[UserId], event: LifeEvent) -> Option<GameOver> {
+    let characters = &state.characters;
+    let character_type = |user_id: &UserId| characters.get(user_id).map(|i| i.character_type);
+    let is = |user_id: &UserId, name: &str| characters.get(user_id).is_some_and(|i| i.is(name));
+
+    if let LifeEvent::Executed(executed) = event
+        && is(&executed, "Saint")
+    {
+        return Some(GameOver::SaintExecuted);
+    }
+
+    // Without a known demon there is nothing to decide on
+    if !characters
+        .values()
+        .any(|i| i.character_type == CharacterType::Demon)
+    {
+        return None;
+    }
+
+    if !alive
+        .iter()
+        .any(|i| character_type(i) == Some(CharacterType::Demon))
+    {
+        return Some(GameOver::DemonDied);
+    }
+
+    // Travellers don't count towards the number of living players
+    let living_players = alive.iter().filter(|i| !state.is_traveller(**i)).count();
+
+    if living_players <= 2 {
+        return Some(GameOver::TwoPlayersLeft);
+    }
+
+    if let LifeEvent::NoExecution = event
+        && living_players == 3
+        && alive.iter().any(|i| is(i, "Mayor"))
+    {
+        return Some(GameOver::MayorWin);
+    }
+
+    None
+}
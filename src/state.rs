@@ -1,8 +1,28 @@
-use std::{collections::HashMap, fmt::Display, fs::OpenOptions, num::NonZeroU32};
-
-use poise::serenity_prelude::{ChannelId, MessageId, UserId};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs::OpenOptions,
+    num::NonZeroU32,
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use botc_discord_bot::transcript::GameTimeline;
+use poise::serenity_prelude::{
+    ChannelId, Colour, CreateAttachment, CreateEmbed, MessageId, UserId,
+};
+use rand::{Rng, seq::SliceRandom};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    Error,
+    deadlines::{FormatDeadlines, VoteDeadlines},
+    engine::GameError,
+    gateway::OutgoingMessage,
+    modifiers::{ReminderMap, VoteModifiers},
+    town_square::TOWN_SQUARE_FILENAME,
+};
+
 #[derive(Hash, Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Debug)]
 pub struct CottageNumber(pub NonZeroU32);
 
@@ -11,14 +31,16 @@ impl CottageNumber {
         Some(CottageNumber(NonZeroU32::new(value)?))
     }
 
+    /// The cottage after this one, going back round to cottage 1 after the last one
     pub fn next(self, number_of_players: u32) -> CottageNumber {
-        CottageNumber::new(self.0.get() % number_of_players + 1).unwrap()
+        let offset = self.0.get().checked_rem(number_of_players).unwrap_or(0);
+        CottageNumber(NonZeroU32::MIN.saturating_add(offset))
     }
 }
 
 pub type PlayerMap = HashMap<CottageNumber, (UserId, ChannelId)>;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum VoteState {
     None,
     HandRaised,
@@ -27,7 +49,7 @@ pub enum VoteState {
     No,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum DeadState {
     Alive,
     DeadVoteAvailable,
@@ -42,8 +64,25 @@ impl Display for FormatMention {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoteKind {
+    /// A regular nomination, only living players and those with a ghost vote may vote
+    #[default]
+    Execution,
+    /// A call to exile a traveller, anyone may vote and ghost votes are not used up
+    Exile,
+}
+
+impl VoteKind {
+    pub fn uses_ghost_votes(self) -> bool {
+        self == VoteKind::Execution
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Vote {
+    #[serde(default)]
+    pub kind: VoteKind,
     pub nominator: UserId,
     pub nominee: UserId,
 
@@ -55,11 +94,211 @@ pub struct Vote {
     pub vote_state: HashMap<UserId, VoteState>,
     #[serde(default)]
     pub dead_state: HashMap<UserId, DeadState>,
+    /// Dead players who used up their ghost vote in this vote
+    #[serde(default)]
+    pub spent_ghost_votes: HashSet<UserId>,
+    /// Whether the Butler's master had their hand up when the clock hand reached the Butler
+    #[serde(default)]
+    pub butler_master_voting: Option<bool>,
 
     pub description: String,
 
+    /// Organ Grinder games hide who voted for what, only the storyteller sees the ballot
+    #[serde(default)]
+    pub secret: bool,
+
     pub message_id: MessageId,
     pub channel_id: ChannelId,
+
+    #[serde(default)]
+    pub thread: Option<VoteThread>,
+    #[serde(default)]
+    pub deadlines: VoteDeadlines,
+}
+
+/// What the nominator or nominee says for themselves
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Statement {
+    Accusation,
+    Defense,
+}
+
+impl Statement {
+    pub fn name(self) -> &'static str {
+        match self {
+            Statement::Accusation => "accusation",
+            Statement::Defense => "defense",
+        }
+    }
+}
+
+/// A discussion thread on the vote message. The first thing the nominator and nominee post there
+/// is put to the storyteller as their accusation and defense.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoteThread {
+    pub thread_id: ChannelId,
+    #[serde(default)]
+    pub pending_accusation: Option<String>,
+    #[serde(default)]
+    pub pending_defense: Option<String>,
+}
+
+impl VoteThread {
+    pub fn pending(&mut self, statement: Statement) -> &mut Option<String> {
+        match statement {
+            Statement::Accusation => &mut self.pending_accusation,
+            Statement::Defense => &mut self.pending_defense,
+        }
+    }
+}
+
+impl Vote {
+    pub fn statement(&mut self, statement: Statement) -> &mut String {
+        match statement {
+            Statement::Accusation => &mut self.accusation,
+            Statement::Defense => &mut self.defense,
+        }
+    }
+
+    /// Whether a Yes from this player counts: dead players can't vote in an execution once their
+    /// ghost vote is gone, unless they spent it on this vote
+    pub fn may_vote(&self, user_id: UserId) -> bool {
+        !self.kind.uses_ghost_votes()
+            || !matches!(self.dead_state.get(&user_id), Some(DeadState::DeadVoteUsed))
+            || self.spent_ghost_votes.contains(&user_id)
+    }
+
+    /// Every seated player has voted
+    pub fn is_finished(&self, players: &PlayerMap) -> bool {
+        players.values().all(|(user_id, _)| {
+            matches!(
+                self.vote_state.get(user_id),
+                Some(VoteState::Yes | VoteState::No)
+            )
+        })
+    }
+
+    pub fn is_alive(&self, user_id: UserId) -> bool {
+        matches!(
+            self.dead_state.get(&user_id).unwrap_or(&DeadState::Alive),
+            DeadState::Alive
+        )
+    }
+
+    /// Votes needed to put the nominee on the block, or to exile a traveller: half the living
+    /// players, rounded up
+    pub fn threshold(&self, players: &PlayerMap) -> u32 {
+        let alive = players
+            .values()
+            .filter(|(user_id, _)| self.is_alive(*user_id))
+            .count() as u32;
+        alive.div_ceil(2)
+    }
+
+    pub fn record(&self, players: &PlayerMap, modifiers: &VoteModifiers) -> VoteRecord {
+        VoteRecord {
+            nominator: self.nominator,
+            nominee: self.nominee,
+            votes: modifiers.tally(self).votes,
+            threshold: modifiers.threshold(self, players),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoteRecord {
+    pub nominator: UserId,
+    pub nominee: UserId,
+    pub votes: u32,
+    pub threshold: u32,
+}
+
+#[derive(Debug)]
+pub enum Execution<'a> {
+    Nobody,
+    Tied(Vec<&'a VoteRecord>),
+    Executed(&'a VoteRecord),
+}
+
+/// The nominee with the most votes is executed, provided they reached the threshold and nobody
+/// else has the same number of votes
+pub fn determine_execution(history: &[VoteRecord]) -> Execution<'_> {
+    let qualifying = history.iter().filter(|i| i.votes >= i.threshold);
+    let Some(most_votes) = qualifying.clone().map(|i| i.votes).max() else {
+        return Execution::Nobody;
+    };
+
+    let mut on_the_block: Vec<_> = qualifying.filter(|i| i.votes == most_votes).collect();
+    if on_the_block.len() == 1 {
+        Execution::Executed(on_the_block.remove(0))
+    } else {
+        Execution::Tied(on_the_block)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    #[default]
+    Day,
+    Night,
+    GameOver,
+}
+
+#[derive(Serialize, Deserialize, poise::ChoiceParameter, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharacterType {
+    Townsfolk,
+    Outsider,
+    Minion,
+    Demon,
+    Traveller,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Character {
+    pub name: String,
+    pub character_type: CharacterType,
+}
+
+impl Character {
+    pub fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+/// A private thread between two players that storytellers can read along with
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Whisper {
+    pub players: [UserId; 2],
+    pub thread_id: ChannelId,
+    /// Every time one of the players started whispering in this thread
+    pub started: Vec<SystemTime>,
+    /// Messages the two players sent in this thread
+    #[serde(default)]
+    pub messages: u32,
+}
+
+impl Whisper {
+    pub fn is_between(&self, a: UserId, b: UserId) -> bool {
+        self.players == [a, b] || self.players == [b, a]
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Whispers {
+    /// Publicly announce who is whispering to whom
+    pub announce: bool,
+    /// Lock whisper threads during the night
+    pub close_at_night: bool,
+    pub threads: Vec<Whisper>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct SignUps {
+    /// The message with the Join and Leave buttons
+    pub message: Option<(ChannelId, MessageId)>,
+    /// Everyone who signed up and isn't seated yet, in join order. Once the table is full this is
+    /// the waitlist for substitutes.
+    pub queue: Vec<UserId>,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
@@ -67,26 +306,218 @@ pub struct State {
     pub players: PlayerMap,
     pub number_of_players: u32,
     pub current_vote: Option<Vote>,
+    #[serde(default)]
+    pub day: u32,
+    #[serde(default)]
+    pub phase: Phase,
+    #[serde(default)]
+    pub vote_history: Vec<VoteRecord>,
+    #[serde(default)]
+    pub characters: HashMap<UserId, Character>,
+    #[serde(default)]
+    pub travellers: HashSet<UserId>,
+    #[serde(default)]
+    pub reminders: ReminderMap,
+    #[serde(default)]
+    pub timeline: GameTimeline,
+    #[serde(default)]
+    pub whispers: Whispers,
+    /// Cottage channels the bot created itself and should clean up after the game
+    #[serde(default)]
+    pub provisioned_cottages: HashSet<ChannelId>,
+    #[serde(default)]
+    pub signups: SignUps,
+    /// Players who'd rather not be pinged when the clock hand reaches them
+    #[serde(default)]
+    pub clock_hand_ping_opt_outs: HashSet<UserId>,
+    /// Names drawn on the town square, looked up the first time each player is drawn
+    #[serde(default)]
+    pub display_names: HashMap<UserId, String>,
+    /// Where [`State::save`] writes to, nothing is written when unset
+    #[serde(skip)]
+    pub save_path: Option<PathBuf>,
+    /// Counts renders of the vote message, so a slow render doesn't overwrite a newer one
+    #[serde(skip)]
+    pub vote_renders: u64,
 }
 
+/// Who sits in each cottage, in cottage order
+pub type Seating = Vec<Option<(UserId, ChannelId)>>;
+
 impl State {
-    pub fn save(&self) {
-        serde_yml::to_writer(
-            OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open("state.yaml")
-                .unwrap(),
-            self,
-        )
-        .unwrap()
+    /// Travellers are flagged when they're seated, or have a Traveller character
+    pub fn is_traveller(&self, user_id: UserId) -> bool {
+        self.travellers.contains(&user_id)
+            || self
+                .characters
+                .get(&user_id)
+                .is_some_and(|i| i.character_type == CharacterType::Traveller)
+    }
+
+    /// The cottage with this number, if it exists with the current number of players
+    pub fn cottage(&self, number: u32) -> Option<CottageNumber> {
+        CottageNumber::new(number).filter(|i| i.0.get() <= self.number_of_players)
+    }
+
+    pub fn seating(&self) -> Seating {
+        (1..=self.number_of_players)
+            .map(|i| {
+                CottageNumber::new(i)
+                    .and_then(|i| self.players.get(&i))
+                    .copied()
+            })
+            .collect()
+    }
+
+    /// Seats everyone in order, the number of players becomes the length of the seating
+    pub fn set_seating(&mut self, seating: Seating) {
+        self.number_of_players = seating.len() as u32;
+        self.players = seating
+            .into_iter()
+            .zip(1..)
+            .filter_map(|(seat, i)| Some((CottageNumber::new(i)?, seat?)))
+            .collect();
+    }
+
+    /// Cottages can't change while the clock hand is going round
+    pub fn check_seats_unlocked(&self) -> Result<(), GameError> {
+        match &self.current_vote {
+            Some(vote) if !vote.is_finished(&self.players) => Err(GameError::VoteInProgress),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn swap_seats(&mut self, a: CottageNumber, b: CottageNumber) -> Result<(), GameError> {
+        self.check_seats_unlocked()?;
+        let first = self.players.remove(&a);
+        let second = self.players.remove(&b);
+        if let Some(player) = first {
+            self.players.insert(b, player);
+        }
+        if let Some(player) = second {
+            self.players.insert(a, player);
+        }
+        Ok(())
+    }
+
+    /// Randomly redistributes the seated players over every cottage
+    pub fn shuffle_seats(&mut self, rng: &mut impl Rng) -> Result<(), GameError> {
+        self.check_seats_unlocked()?;
+        let mut seating = self.seating();
+        seating.shuffle(rng);
+        self.set_seating(seating);
+        Ok(())
+    }
+
+    /// Moves a player to a cottage, shifting everyone in between over by one seat. Players that
+    /// weren't seated yet are inserted, which adds a cottage.
+    pub fn move_player(
+        &mut self,
+        player: (UserId, ChannelId),
+        to: CottageNumber,
+    ) -> Result<(), GameError> {
+        self.check_seats_unlocked()?;
+        let mut seating = self.seating();
+        if let Some(index) = seating
+            .iter()
+            .position(|seat| seat.is_some_and(|(user_id, _)| user_id == player.0))
+        {
+            seating.remove(index);
+        }
+        let index = (to.0.get() as usize - 1).min(seating.len());
+        seating.insert(index, Some(player));
+        self.set_seating(seating);
+        Ok(())
+    }
+
+    pub fn is_seated(&self, user_id: UserId) -> bool {
+        self.players.values().any(|(i, _)| *i == user_id)
+    }
+
+    /// Hands everything tied to a player over to someone else, returning the seat they took over
+    pub fn substitute(&mut self, old: UserId, new: UserId) -> Option<(CottageNumber, ChannelId)> {
+        let (cottage, (user_id, channel_id)) = self
+            .players
+            .iter_mut()
+            .find(|(_, (user_id, _))| *user_id == old)?;
+        *user_id = new;
+        let seat = (*cottage, *channel_id);
+
+        if let Some(character) = self.characters.remove(&old) {
+            self.characters.insert(new, character);
+        }
+        if self.travellers.remove(&old) {
+            self.travellers.insert(new);
+        }
+        if let Some(reminders) = self.reminders.remove(&old) {
+            self.reminders.insert(new, reminders);
+        }
+        let replace = |user_id: &mut UserId| {
+            if *user_id == old {
+                *user_id = new;
+            }
+        };
+        if let Some(vote) = &mut self.current_vote {
+            if let Some(vote_state) = vote.vote_state.remove(&old) {
+                vote.vote_state.insert(new, vote_state);
+            }
+            if let Some(dead_state) = vote.dead_state.remove(&old) {
+                vote.dead_state.insert(new, dead_state);
+            }
+            if vote.spent_ghost_votes.remove(&old) {
+                vote.spent_ghost_votes.insert(new);
+            }
+            replace(&mut vote.nominator);
+            replace(&mut vote.nominee);
+        }
+        for record in &mut self.vote_history {
+            replace(&mut record.nominator);
+            replace(&mut record.nominee);
+        }
+        for whisper in &mut self.whispers.threads {
+            whisper.players.iter_mut().for_each(replace);
+        }
+        self.signups.queue.retain(|i| *i != new);
+
+        Some(seat)
+    }
+
+    /// Cottages nobody has been assigned to yet
+    pub fn empty_cottages(&self) -> Vec<CottageNumber> {
+        (1..=self.number_of_players)
+            .filter_map(CottageNumber::new)
+            .filter(|i| !self.players.contains_key(i))
+            .collect()
+    }
+
+    pub fn vote_modifiers(&self) -> VoteModifiers<'_> {
+        VoteModifiers {
+            characters: &self.characters,
+            reminders: &self.reminders,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let Some(save_path) = &self.save_path else {
+            return Ok(());
+        };
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(save_path)
+            .map_err(|e| {
+                Error::Persistence(format!("Couldn't open {}: {e}", save_path.display()))
+            })?;
+        serde_yml::to_writer(file, self)
+            .map_err(|e| Error::Persistence(format!("Couldn't save the game: {e}")))
     }
 }
 
 pub fn format_vote(
     players: &PlayerMap,
     Vote {
+        kind,
         nominator,
         nominee,
         clock_hand,
@@ -95,13 +526,15 @@ pub fn format_vote(
         vote_state,
         description,
         dead_state,
+        secret,
+        deadlines,
         ..
     }: &Vote,
     number_of_players: u32,
 ) -> String {
     format!(
         r"
-{} nominates {}
+{} {} {}{}
 
 **Accusation:**
 > {accusation}
@@ -109,32 +542,182 @@ pub fn format_vote(
 > {defense}
 
 {}
-{description}
+{description}{}
 
     ",
         FormatMention(*nominator),
+        match kind {
+            VoteKind::Execution => "nominates",
+            VoteKind::Exile => "calls for the exile of",
+        },
         FormatMention(*nominee),
+        if *secret { " (Secret Ballot)" } else { "" },
         FormatVotes {
             vote_state,
             players,
             nominee: *nominee,
             clock_hand: *clock_hand,
             number_of_players,
-            dead_state
+            dead_state,
+            secret: *secret,
+        },
+        FormatDeadlines(deadlines),
+    )
+}
+
+/// How the bot renders the vote and seating messages
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageStyle {
+    #[default]
+    Embed,
+    /// Plain messages with mentions, for clients that don't show embeds
+    PlainText,
+}
+
+/// The full contents of a vote message, for editing it whenever the vote changes. The previous
+/// town square image is replaced if a new one is given.
+pub fn vote_message(
+    players: &PlayerMap,
+    vote: &Vote,
+    number_of_players: u32,
+    modifiers: &VoteModifiers,
+    style: MessageStyle,
+    town_square: Option<CreateAttachment>,
+) -> OutgoingMessage {
+    let has_image = town_square.is_some();
+    let message = match style {
+        MessageStyle::PlainText => {
+            OutgoingMessage::new(format_vote(players, vote, number_of_players))
         }
+        MessageStyle::Embed => {
+            let mut embed = vote_embed(players, vote, number_of_players, modifiers);
+            if has_image {
+                embed = embed.image(format!("attachment://{TOWN_SQUARE_FILENAME}"));
+            }
+            OutgoingMessage::new(vote_header(vote)).embed(embed)
+        }
+    };
+
+    message.attachment(town_square)
+}
+
+fn vote_header(vote: &Vote) -> String {
+    format!(
+        "{} {} {}",
+        FormatMention(vote.nominator),
+        match vote.kind {
+            VoteKind::Execution => "nominates",
+            VoteKind::Exile => "calls for the exile of",
+        },
+        FormatMention(vote.nominee),
     )
 }
 
+/// The vote as an embed. The votes and threshold are counted the same way as the recorded result,
+/// with every character ability applied.
+pub fn vote_embed(
+    players: &PlayerMap,
+    vote: &Vote,
+    number_of_players: u32,
+    modifiers: &VoteModifiers,
+) -> CreateEmbed {
+    let or_placeholder = |text: &str| {
+        if text.is_empty() {
+            "*None yet*".to_owned()
+        } else {
+            text.to_owned()
+        }
+    };
+
+    let votes = modifiers.tally(vote).votes;
+    let threshold = modifiers.threshold(vote, players);
+    let finished = vote.is_finished(players);
+
+    CreateEmbed::new()
+        .title(match (vote.kind, vote.secret) {
+            (VoteKind::Execution, false) => "Nomination",
+            (VoteKind::Execution, true) => "Nomination (Secret Ballot)",
+            (VoteKind::Exile, false) => "Exile",
+            (VoteKind::Exile, true) => "Exile (Secret Ballot)",
+        })
+        .description(format!(
+            "{}\n{}{}\n\n{}",
+            vote_header(vote),
+            vote.description,
+            FormatDeadlines(&vote.deadlines),
+            FormatVotes {
+                vote_state: &vote.vote_state,
+                players,
+                nominee: vote.nominee,
+                clock_hand: vote.clock_hand,
+                number_of_players,
+                dead_state: &vote.dead_state,
+                secret: vote.secret,
+            }
+        ))
+        .field("Accusation", or_placeholder(&vote.accusation), false)
+        .field("Defense", or_placeholder(&vote.defense), false)
+        .field(
+            "Votes",
+            if vote.secret {
+                "Hidden".to_owned()
+            } else {
+                votes.to_string()
+            },
+            true,
+        )
+        .field("Threshold", threshold.to_string(), true)
+        .colour(if !finished {
+            Colour::BLURPLE
+        } else if vote.secret {
+            Colour::DARK_GREY
+        } else if votes >= threshold {
+            Colour::RED
+        } else {
+            Colour::DARK_GREEN
+        })
+}
+
+pub fn cottages_embed(state: &State) -> CreateEmbed {
+    CreateEmbed::new()
+        .title("Current Cottage Assignment")
+        .description(PrintCottages(state).to_string())
+        .colour(Colour::BLURPLE)
+}
+
+/// Renders every player's vote regardless of secret ballots, for the storyteller's eyes only
+pub fn format_ballot(players: &PlayerMap, vote: &Vote, number_of_players: u32) -> String {
+    FormatVotes {
+        vote_state: &vote.vote_state,
+        dead_state: &vote.dead_state,
+        players,
+        nominee: vote.nominee,
+        clock_hand: vote.clock_hand,
+        number_of_players,
+        secret: false,
+    }
+    .to_string()
+}
+
 pub struct PrintCottages<'a>(pub &'a State);
 
 impl<'a> Display for PrintCottages<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for i in 1..self.0.number_of_players + 1 {
             write!(f, "{i}: ")?;
-            match self.0.players.get(&CottageNumber::new(i).unwrap()) {
-                Some((player, channel)) => {
-                    writeln!(f, "{} <#{}>", FormatMention(*player), channel)?
-                }
+            match CottageNumber::new(i).and_then(|i| self.0.players.get(&i)) {
+                Some((player, channel)) => writeln!(
+                    f,
+                    "{} <#{}>{}",
+                    FormatMention(*player),
+                    channel,
+                    if self.0.is_traveller(*player) {
+                        " (Traveller)"
+                    } else {
+                        ""
+                    }
+                )?,
                 None => writeln!(f, "unassigned")?,
             };
         }
@@ -149,6 +732,7 @@ struct FormatVotes<'a> {
     nominee: UserId,
     clock_hand: CottageNumber,
     number_of_players: u32,
+    secret: bool,
 }
 
 impl<'a> Display for FormatVotes<'a> {
@@ -165,13 +749,12 @@ impl<'a> Display for FormatVotes<'a> {
         let number_of_players = self.number_of_players;
         let mut clockhand_player = None;
 
+        let mut cottage = start_player_index;
         for i in 0..number_of_players {
-            let cottage =
-                CottageNumber::new((i + start_player_index.0.get()) % number_of_players + 1)
-                    .unwrap();
+            cottage = cottage.next(number_of_players);
 
             let Some(player_id) = self.players.get(&cottage).map(|i| i.0) else {
-                write!(f, "[Empty Cottage]\n")?;
+                writeln!(f, "[Empty Cottage]")?;
                 continue;
             };
 
@@ -188,11 +771,17 @@ impl<'a> Display for FormatVotes<'a> {
                 FormatMention(player_id),
                 match dead_state {
                     DeadState::Alive => "",
+                    // A ghost vote being used up would give away how they voted
+                    DeadState::DeadVoteAvailable | DeadState::DeadVoteUsed if self.secret => {
+                        " (Dead)"
+                    }
                     DeadState::DeadVoteAvailable => " (Dead)",
                     DeadState::DeadVoteUsed => " (Dead Vote Used)",
                 },
                 match vote_state {
                     None => " ",
+                    Some(VoteState::Yes | VoteState::No) if self.secret => "🗳️",
+                    Some(VoteState::HandRaised | VoteState::HandLowered) if self.secret => " ",
                     Some(VoteState::HandRaised) => "🙋",
                     Some(VoteState::HandLowered) => "🙅‍♂️",
                     Some(VoteState::Yes) => "✅",
@@ -214,3 +803,29 @@ impl<'a> Display for FormatVotes<'a> {
         Ok(())
     }
 }
+
+pub struct PrintSignUps<'a>(pub &'a State);
+
+impl<'a> Display for PrintSignUps<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let open_seats = self.0.empty_cottages().len();
+        let queue = &self.0.signups.queue;
+
+        writeln!(
+            f,
+            "**Sign-ups** ({} seated, {} of {open_seats} open seats taken)",
+            self.0.players.len(),
+            queue.len().min(open_seats)
+        )?;
+        for (i, player) in queue.iter().take(open_seats).enumerate() {
+            writeln!(f, "{}. {}", i + 1, FormatMention(*player))?;
+        }
+        if queue.len() > open_seats {
+            writeln!(f, "**Waitlist**")?;
+            for (i, player) in queue.iter().skip(open_seats).enumerate() {
+                writeln!(f, "{}. {}", i + 1, FormatMention(*player))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! The custom IDs of the buttons and forms the game posts, and what pressing them is routed to
+
+use poise::serenity_prelude::MessageId;
+use tokio::sync::RwLock;
+
+use super::{TOWN_SQUARE, config, nomination, player, seated_game};
+use crate::{
+    game::{Button, Nomination, parse_statement_modal, statement_modal_id},
+    state::{MessageStyle, State, Statement},
+};
+
+#[tokio::test]
+async fn every_posted_button_is_routed() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    game.open_signups(TOWN_SQUARE).await.unwrap();
+    let nomination = Nomination {
+        thread: true,
+        ..nomination(1, 2)
+    };
+    game.start_vote(TOWN_SQUARE, nomination).await.unwrap();
+    let (vote, thread_id) = {
+        let state = state.read().await;
+        let vote = state.current_vote.as_ref().unwrap();
+        (vote.message_id, vote.thread.as_ref().unwrap().thread_id)
+    };
+    game.thread_message(thread_id, player(2), "I'm the Chef")
+        .await
+        .unwrap();
+
+    let buttons: Vec<_> = game
+        .gateway
+        .messages
+        .lock()
+        .unwrap()
+        .iter()
+        .flat_map(|(_, i)| i.buttons.clone())
+        .map(|i| Button::parse(&i).unwrap_or_else(|| panic!("{i} isn't routed")))
+        .collect();
+    assert_eq!(
+        buttons,
+        [
+            Button::SignUp { join: true },
+            Button::SignUp { join: false },
+            Button::Hand { raised: true },
+            Button::Hand { raised: false },
+            Button::WriteStatement(Statement::Accusation),
+            Button::WriteStatement(Statement::Defense),
+            // Player 3 is pinged as the first voter
+            Button::ClockHand { vote, yes: true },
+            Button::ClockHand { vote, yes: false },
+            Button::ResolveStatement {
+                vote,
+                statement: Statement::Defense,
+                confirm: true,
+            },
+            Button::ResolveStatement {
+                vote,
+                statement: Statement::Defense,
+                confirm: false,
+            },
+        ]
+    );
+    for button in buttons {
+        assert_eq!(Button::parse(&button.custom_id()), Some(button));
+    }
+}
+
+#[test]
+fn other_buttons_are_left_to_their_command() {
+    let ignored = [
+        "game_over_confirm",
+        "game_over_dismiss",
+        "clock_hand_maybe_5",
+        "clock_hand_yes_0",
+        "clock_hand_yes",
+        "statement_confirm_alibi_5",
+        "statement_use_accusation_5",
+        "statement_confirm_accusation_vote",
+    ];
+    for custom_id in ignored {
+        assert_eq!(Button::parse(custom_id), None, "{custom_id}");
+    }
+
+    assert_eq!(
+        Button::parse("clock_hand_no_5"),
+        Some(Button::ClockHand {
+            vote: MessageId::new(5),
+            yes: false
+        })
+    );
+}
+
+#[test]
+fn statement_forms_are_routed_to_their_statement() {
+    for statement in [Statement::Accusation, Statement::Defense] {
+        assert_eq!(
+            parse_statement_modal(&statement_modal_id(statement)),
+            Some(statement)
+        );
+    }
+    assert_eq!(parse_statement_modal("accusation_button"), None);
+}
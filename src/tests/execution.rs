@@ -0,0 +1,80 @@
+use poise::serenity_prelude::UserId;
+
+use super::{player, players, vote};
+use crate::state::{DeadState, Execution, VoteRecord, determine_execution};
+
+fn record(nominee: u64, votes: u32, threshold: u32) -> VoteRecord {
+    VoteRecord {
+        nominator: player(1),
+        nominee: player(nominee),
+        votes,
+        threshold,
+    }
+}
+
+fn executed(history: &[VoteRecord]) -> Option<UserId> {
+    match determine_execution(history) {
+        Execution::Executed(record) => Some(record.nominee),
+        _ => None,
+    }
+}
+
+#[test]
+fn threshold_is_half_the_living_rounded_up() {
+    let players = players(7);
+    assert_eq!(vote(1, 2, &[]).threshold(&players), 4);
+
+    let dead = [(3, DeadState::DeadVoteAvailable)];
+    assert_eq!(vote(1, 2, &dead).threshold(&players), 3);
+
+    let dead = [
+        (3, DeadState::DeadVoteAvailable),
+        (4, DeadState::DeadVoteUsed),
+    ];
+    assert_eq!(vote(1, 2, &dead).threshold(&players), 3);
+}
+
+#[test]
+fn nobody_is_executed_without_reaching_the_threshold() {
+    assert!(matches!(determine_execution(&[]), Execution::Nobody));
+    assert!(matches!(
+        determine_execution(&[record(2, 3, 4), record(3, 1, 4)]),
+        Execution::Nobody
+    ));
+}
+
+#[test]
+fn the_most_votes_are_executed() {
+    assert_eq!(
+        executed(&[record(2, 4, 4), record(3, 5, 4), record(4, 2, 4)]),
+        Some(player(3))
+    );
+}
+
+#[test]
+fn a_later_higher_vote_beats_an_earlier_one() {
+    assert_eq!(
+        executed(&[record(2, 4, 4), record(3, 6, 4)]),
+        Some(player(3))
+    );
+    assert_eq!(
+        executed(&[record(2, 6, 4), record(3, 4, 4)]),
+        Some(player(2))
+    );
+}
+
+#[test]
+fn a_tie_executes_nobody() {
+    let history = [record(2, 5, 4), record(3, 5, 4), record(4, 4, 4)];
+    let Execution::Tied(tied) = determine_execution(&history) else {
+        panic!("expected a tie");
+    };
+    let tied: Vec<_> = tied.iter().map(|i| i.nominee).collect();
+    assert_eq!(tied, [player(2), player(3)]);
+
+    // A tie below the threshold is no tie at all
+    assert!(matches!(
+        determine_execution(&[record(2, 3, 4), record(3, 3, 4)]),
+        Execution::Nobody
+    ));
+}
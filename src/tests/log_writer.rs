@@ -0,0 +1,134 @@
+//! The message log written to a temporary directory and read back segment by segment
+
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use botc_discord_bot::message_log::{
+    LogConfig, LogEvent, LogRecord, log_segments, read_all_segments,
+};
+use poise::serenity_prelude::UserId;
+
+use crate::log_writer::LogWriter;
+
+/// An empty directory only this test writes to
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("botc-log-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn substitution(cottage: u32) -> LogEvent {
+    LogEvent::Substitution {
+        cottage,
+        old: UserId::new(1),
+        new: UserId::new(2),
+    }
+}
+
+/// The cottage of every substitution in the log, in the order they were read
+fn cottages(path: &Path) -> Vec<u32> {
+    read_all_segments(path)
+        .unwrap()
+        .map(|record| match record.unwrap().event {
+            LogEvent::Substitution { cottage, .. } => cottage,
+            event => panic!("unexpected {event:?}"),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn segments_rotate_once_they_are_full() {
+    let dir = temp_dir("rotation");
+    let path = dir.join("message_log.jsonl");
+    let writer = LogWriter::spawn(LogConfig {
+        path: path.clone(),
+        // Room for a single record
+        max_size: Some(1),
+        ..LogConfig::default()
+    })
+    .unwrap();
+
+    for cottage in 1..=3 {
+        writer.write(substitution(cottage)).await;
+    }
+    writer.flush().await;
+
+    assert_eq!(log_segments(&path).unwrap().len(), 3);
+    assert_eq!(cottages(&path), [1, 2, 3]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn closed_segments_are_compressed() {
+    let dir = temp_dir("gzip");
+    let path = dir.join("message_log.jsonl");
+    let writer = LogWriter::spawn(LogConfig {
+        path: path.clone(),
+        compress: true,
+        ..LogConfig::default()
+    })
+    .unwrap();
+
+    writer.write(substitution(1)).await;
+    writer.rotate().await;
+    writer.write(substitution(2)).await;
+    writer.flush().await;
+
+    // Compression happens on its own thread
+    let mut segments = vec![];
+    for _ in 0..100 {
+        segments = log_segments(&path).unwrap();
+        if segments[0].extension().is_some_and(|i| i == "gz") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    assert_eq!(segments.len(), 2);
+    assert!(segments[0].extension().is_some_and(|i| i == "gz"));
+    assert_eq!(segments[1], path);
+    assert_eq!(cottages(&path), [1, 2]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn segments_being_compressed_are_read_once() {
+    let dir = temp_dir("dedup");
+    let path = dir.join("message_log.jsonl");
+    let write = |path: PathBuf, cottage: u32| {
+        LogRecord::new(substitution(cottage))
+            .write_to(&mut File::create(path).unwrap())
+            .unwrap();
+    };
+
+    // Halfway through compressing the first segment both copies exist, next to the partial file
+    // of the second one
+    write(dir.join("message_log.1000.jsonl"), 1);
+    let mut encoder = flate2::write::GzEncoder::new(
+        File::create(dir.join("message_log.1000.jsonl.gz")).unwrap(),
+        flate2::Compression::default(),
+    );
+    LogRecord::new(substitution(1))
+        .write_to(&mut encoder)
+        .unwrap();
+    encoder.finish().unwrap().flush().unwrap();
+    write(dir.join("message_log.2000.jsonl"), 2);
+    write(dir.join("message_log.2000.jsonl.gz.tmp"), 2);
+    write(path.clone(), 3);
+
+    assert_eq!(
+        log_segments(&path).unwrap(),
+        [
+            dir.join("message_log.1000.jsonl"),
+            dir.join("message_log.2000.jsonl"),
+            path.clone(),
+        ]
+    );
+    assert_eq!(cottages(&path), [1, 2, 3]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,77 @@
+//! Reading `message_log.jsonl` back, in both the legacy and the current format
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use botc_discord_bot::message_log::{LOG_VERSION, LogEvent, LogRecord, parse_line};
+use poise::serenity_prelude::{ChannelId, MessageId, User, UserId};
+
+#[test]
+fn legacy_array_lines_are_read_as_version_0() {
+    let line = r#"["message_delete",{"secs_since_epoch":1700000000,"nanos_since_epoch":0},"10","20",null]"#;
+    let record = parse_line(line).unwrap();
+
+    assert_eq!(record.version, 0);
+    assert_eq!(record.time, UNIX_EPOCH + Duration::from_secs(1700000000));
+    let LogEvent::MessageDelete {
+        channel_id,
+        message_id,
+        guild_id,
+    } = record.event
+    else {
+        panic!("expected a deleted message, got {:?}", record.event);
+    };
+    assert_eq!(channel_id, ChannelId::new(10));
+    assert_eq!(message_id, MessageId::new(20));
+    assert_eq!(guild_id, None);
+}
+
+#[test]
+fn current_lines_keep_their_version() {
+    let line = r#"{"version":1,"time":{"secs_since_epoch":1700000000,"nanos_since_epoch":0},"type":"substitution","cottage":3,"old":"1","new":"2"}"#;
+    let record = parse_line(line).unwrap();
+
+    assert_eq!(record.version, 1);
+    let LogEvent::Substitution { cottage, old, new } = record.event else {
+        panic!("expected a substitution, got {:?}", record.event);
+    };
+    assert_eq!(cottage, 3);
+    assert_eq!(old, UserId::new(1));
+    assert_eq!(new, UserId::new(2));
+}
+
+#[test]
+fn written_records_parse_back() {
+    let record = LogRecord::new(LogEvent::NewMessage {
+        message_id: MessageId::new(20),
+        content: "I'm the Chef\nand I got a 1".to_owned(),
+        author: User::default(),
+        channel_id: ChannelId::new(10),
+        thread: Some((ChannelId::new(11), "Whispers".to_owned())),
+        attachments: vec!["https://example.com/grimoire.png".to_owned()],
+    });
+    let mut written = vec![];
+    record.write_to(&mut written).unwrap();
+
+    let written = String::from_utf8(written).unwrap();
+    assert_eq!(written.lines().count(), 1);
+    let parsed = parse_line(written.trim_end()).unwrap();
+
+    assert_eq!(parsed.version, LOG_VERSION);
+    assert_eq!(parsed.time, record.time);
+    let LogEvent::NewMessage {
+        message_id,
+        content,
+        channel_id,
+        thread,
+        attachments,
+        ..
+    } = parsed.event
+    else {
+        panic!("expected a new message, got {:?}", parsed.event);
+    };
+    assert_eq!(message_id, MessageId::new(20));
+    assert_eq!(content, "I'm the Chef\nand I got a 1");
+    assert_eq!(channel_id, ChannelId::new(10));
+    assert_eq!(thread, Some((ChannelId::new(11), "Whispers".to_owned())));
+    assert_eq!(attachments, vec!["https://example.com/grimoire.png"]);
+}
@@ -0,0 +1,134 @@
+//! Games played start to finish against [`FakeGateway`]
+
+mod buttons;
+mod execution;
+mod log_writer;
+mod message_log;
+mod modifiers;
+mod properties;
+mod restart;
+mod rules;
+mod scenarios;
+mod seating;
+mod server;
+mod threads;
+mod transcript;
+mod votes;
+
+use std::collections::{HashMap, HashSet};
+
+use poise::serenity_prelude::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use tokio::sync::RwLock;
+
+use crate::{
+    Config,
+    game::{Game, Nomination},
+    gateway::fake::FakeGateway,
+    state::{CottageNumber, DeadState, MessageStyle, PlayerMap, State, Vote, VoteKind},
+    town_square::TownSquareConfig,
+};
+
+pub const STORYTELLER: RoleId = RoleId::new(1);
+pub const DEAD: RoleId = RoleId::new(2);
+pub const GHOST_VOTE: RoleId = RoleId::new(3);
+pub const TOWN_SQUARE: ChannelId = ChannelId::new(10);
+/// The buttons every vote message carries
+pub const VOTE_BUTTONS: [&str; 4] = [
+    "hand_up_button",
+    "hand_down_button",
+    "accusation_button",
+    "defense_button",
+];
+
+pub fn config(message_style: MessageStyle) -> Config {
+    Config {
+        token: String::new(),
+        guild_id: GuildId::new(100),
+        storyteller_role: STORYTELLER,
+        dead_role: DEAD,
+        ghost_vote_available_role: GHOST_VOTE,
+        log: Default::default(),
+        whisper_channel: None,
+        cottage_category: None,
+        cottage_archive_category: None,
+        message_style,
+        town_square: TownSquareConfig {
+            enabled: false,
+            font_dir: None,
+        },
+        deadlines: Default::default(),
+    }
+}
+
+/// Player `n` is user `n`
+pub fn player(n: u64) -> UserId {
+    UserId::new(n)
+}
+
+/// Players 1 to `n` in the cottages with the same number
+pub fn players(n: u64) -> PlayerMap {
+    (1..=n)
+        .map(|i| {
+            (
+                CottageNumber::new(i as u32).unwrap(),
+                (player(i), ChannelId::new(i)),
+            )
+        })
+        .collect()
+}
+
+/// An execution vote on `nominee` that nobody has voted in yet, with everyone in `dead` dead
+pub fn vote(nominator: u64, nominee: u64, dead: &[(u64, DeadState)]) -> Vote {
+    Vote {
+        kind: VoteKind::Execution,
+        nominator: player(nominator),
+        nominee: player(nominee),
+        description: String::new(),
+        accusation: String::new(),
+        defense: String::new(),
+        clock_hand: CottageNumber::new(nominee as u32).unwrap(),
+        vote_state: HashMap::new(),
+        dead_state: dead.iter().map(|(n, i)| (player(*n), *i)).collect(),
+        spent_ghost_votes: HashSet::new(),
+        butler_master_voting: None,
+        secret: false,
+        message_id: MessageId::new(1),
+        channel_id: TOWN_SQUARE,
+        thread: None,
+        deadlines: Default::default(),
+    }
+}
+
+/// An execution nomination without a thread
+pub fn nomination(nominator: u64, nominee: u64) -> Nomination {
+    Nomination {
+        nominator: player(nominator),
+        nominee: player(nominee),
+        description: String::new(),
+        kind: VoteKind::Execution,
+        secret: false,
+        thread: false,
+    }
+}
+
+/// A game with `number_of_players` cottages, seating players 1 to `seated` in the cottages with
+/// the same number
+pub async fn seated_game<'a>(
+    config: &'a Config,
+    state: &'a RwLock<State>,
+    number_of_players: u32,
+    seated: u64,
+) -> Game<'a, FakeGateway> {
+    state.write().await.number_of_players = number_of_players;
+    let game = Game {
+        gateway: FakeGateway::default(),
+        config,
+        state,
+    };
+    for n in 1..=seated {
+        game.seat_player(n as u32, player(n), None, None)
+            .await
+            .unwrap();
+    }
+    game
+}
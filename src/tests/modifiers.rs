@@ -0,0 +1,136 @@
+use poise::serenity_prelude::UserId;
+
+use super::{player, players, vote};
+use crate::{
+    engine::{self, GameEvent},
+    modifiers::{ReminderMap, ReminderToken},
+    state::{Character, CharacterType, DeadState, State, VoteState},
+};
+
+/// Four players with a vote on player 4 open, so the clock hand starts on player 1
+fn state(dead: &[(u64, DeadState)]) -> State {
+    let mut state = State {
+        players: players(4),
+        number_of_players: 4,
+        ..Default::default()
+    };
+    engine::open_vote(&mut state, vote(1, 4, dead));
+    state.current_vote.as_mut().unwrap().clock_hand = engine::cottage(&state, 1).unwrap();
+    state
+}
+
+fn cast(character: &str, n: u64) -> (UserId, Character) {
+    (
+        player(n),
+        Character {
+            name: character.to_owned(),
+            character_type: CharacterType::Townsfolk,
+        },
+    )
+}
+
+fn remind(n: u64, token: ReminderToken) -> ReminderMap {
+    [(player(n), vec![token])].into_iter().collect()
+}
+
+fn set_votes(state: &mut State, votes: &[(u64, VoteState)]) {
+    let vote = state.current_vote.as_mut().unwrap();
+    for (n, vote_state) in votes {
+        vote.vote_state.insert(player(*n), *vote_state);
+    }
+}
+
+fn tally(state: &State) -> u32 {
+    let vote = state.current_vote.as_ref().unwrap();
+    state.vote_modifiers().tally(vote).votes
+}
+
+#[test]
+fn banshees_vote_twice() {
+    let mut state = state(&[]);
+    state.reminders = remind(1, ReminderToken::BansheeHasAbility);
+    set_votes(&mut state, &[(1, VoteState::Yes), (2, VoteState::Yes)]);
+    assert_eq!(tally(&state), 3);
+}
+
+#[test]
+fn bureaucrats_make_a_vote_count_three_times() {
+    let mut state = state(&[]);
+    state.reminders = remind(2, ReminderToken::BureaucratThreeVotes);
+    set_votes(&mut state, &[(1, VoteState::No), (2, VoteState::Yes)]);
+    assert_eq!(tally(&state), 3);
+}
+
+#[test]
+fn thieves_make_a_vote_count_negatively() {
+    let mut state = state(&[]);
+    state.reminders = remind(3, ReminderToken::ThiefNegativeVote);
+    set_votes(
+        &mut state,
+        &[
+            (1, VoteState::Yes),
+            (2, VoteState::Yes),
+            (3, VoteState::Yes),
+        ],
+    );
+    assert_eq!(tally(&state), 1);
+
+    // The tally never goes below zero
+    set_votes(&mut state, &[(1, VoteState::No), (2, VoteState::No)]);
+    assert_eq!(tally(&state), 0);
+}
+
+#[test]
+fn butlers_follow_their_masters_hand_when_the_clockhand_reaches_them() {
+    for (master_hand, master_vote, counts) in [
+        (VoteState::HandRaised, false, true),
+        (VoteState::HandLowered, true, false),
+    ] {
+        let mut state = state(&[]);
+        state.characters = [cast("Butler", 1)].into_iter().collect();
+        state.reminders = remind(3, ReminderToken::ButlerMaster);
+        set_votes(&mut state, &[(3, master_hand)]);
+
+        // The Butler votes first, their master changes their mind afterwards
+        engine::cast_vote(&mut state, true).unwrap();
+        engine::cast_vote(&mut state, false).unwrap();
+        engine::cast_vote(&mut state, master_vote).unwrap();
+
+        let expected = u32::from(counts) + u32::from(master_vote);
+        assert_eq!(tally(&state), expected, "master's hand {master_hand:?}");
+    }
+}
+
+#[test]
+fn only_the_voudon_and_the_dead_vote_while_the_voudon_lives() {
+    let dead = [
+        (2, DeadState::DeadVoteAvailable),
+        (3, DeadState::DeadVoteUsed),
+    ];
+    let mut state = state(&dead);
+    state.characters = [cast("Voudon", 1)].into_iter().collect();
+
+    let mut events = vec![];
+    for _ in 0..4 {
+        events.extend(engine::cast_vote(&mut state, true).unwrap());
+    }
+    assert!(
+        !events
+            .iter()
+            .any(|i| matches!(i, GameEvent::GhostVoteUsed { .. })),
+        "ghost votes aren't spent"
+    );
+    // The Voudon and both dead players, but not the living player 4
+    assert_eq!(tally(&state), 3);
+
+    let vote = state.current_vote.as_ref().unwrap();
+    let threshold = state.vote_modifiers().threshold(vote, &state.players);
+    assert_eq!(threshold, 1);
+}
+
+#[test]
+fn dead_players_without_a_ghost_vote_dont_count() {
+    let mut state = state(&[(2, DeadState::DeadVoteUsed)]);
+    set_votes(&mut state, &[(1, VoteState::Yes), (2, VoteState::Yes)]);
+    assert_eq!(tally(&state), 1);
+}
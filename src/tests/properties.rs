@@ -0,0 +1,138 @@
+use poise::serenity_prelude::{ChannelId, UserId};
+use proptest::prelude::*;
+
+use super::vote;
+use crate::{
+    engine::{self, GameEvent},
+    state::{CottageNumber, PlayerMap, State, Vote, VoteKind, format_ballot},
+};
+
+/// A table of up to 20 cottages with at least one player, and the cottage of the nominee
+fn table() -> impl Strategy<Value = (u32, PlayerMap, CottageNumber)> {
+    (1..=20u32)
+        .prop_flat_map(|n| {
+            (
+                Just(n),
+                proptest::collection::vec(any::<bool>(), n as usize),
+            )
+        })
+        .prop_filter("nobody is seated", |(_, seated)| seated.contains(&true))
+        .prop_flat_map(|(n, seated)| {
+            let players: PlayerMap = seated
+                .iter()
+                .enumerate()
+                .filter(|(_, seated)| **seated)
+                .map(|(i, _)| {
+                    let cottage = i as u32 + 1;
+                    (
+                        CottageNumber::new(cottage).unwrap(),
+                        (
+                            UserId::new(cottage as u64 + 100),
+                            ChannelId::new(cottage as u64),
+                        ),
+                    )
+                })
+                .collect();
+            let cottages: Vec<_> = players.keys().copied().collect();
+            (Just(n), Just(players), proptest::sample::select(cottages))
+        })
+}
+
+/// Seated players in the order they vote on a nomination of whoever sits in `nominee`
+fn voting_order(players: &PlayerMap, n: u32, nominee: CottageNumber) -> Vec<UserId> {
+    (1..=n)
+        .map(|i| (nominee.0.get() - 1 + i) % n + 1)
+        .filter_map(|i| players.get(&CottageNumber::new(i).unwrap()))
+        .map(|(user_id, _)| *user_id)
+        .collect()
+}
+
+fn open_vote(players: &PlayerMap, n: u32, nominee: CottageNumber) -> State {
+    let mut state = State {
+        players: players.clone(),
+        number_of_players: n,
+        ..Default::default()
+    };
+    let nominee = players[&nominee].0;
+    let clock_hand = engine::check_nomination(&state, nominee, VoteKind::Execution).unwrap();
+    engine::open_vote(
+        &mut state,
+        Vote {
+            clock_hand,
+            ..vote(nominee.get(), nominee.get(), &[])
+        },
+    );
+    state
+}
+
+proptest! {
+    #[test]
+    fn next_stays_on_the_table(n in 1..=100u32, cottage in 1..=200u32) {
+        let next = CottageNumber::new(cottage).unwrap().next(n);
+        prop_assert!((1..=n).contains(&next.0.get()));
+        if cottage <= n {
+            prop_assert_eq!(next.0.get(), cottage % n + 1);
+        }
+    }
+
+    #[test]
+    fn next_goes_round_the_whole_table(n in 1..=100u32, start in 1..=100u32) {
+        let start = CottageNumber::new(start.min(n)).unwrap();
+        let mut cottage = start;
+        let mut visited = vec![];
+        for _ in 0..n {
+            cottage = cottage.next(n);
+            visited.push(cottage.0.get());
+        }
+        prop_assert_eq!(cottage, start);
+        visited.sort();
+        prop_assert_eq!(visited, (1..=n).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn format_votes_lists_everyone_once((n, players, nominee) in table()) {
+        let state = open_vote(&players, n, nominee);
+        let vote = state.current_vote.as_ref().unwrap();
+        // The ballot is the open rendering of `FormatVotes`
+        let rendered = format_ballot(&players, vote, n);
+
+        let rows: Vec<_> = rendered
+            .lines()
+            .filter(|i| !i.starts_with("Clockhand on"))
+            .collect();
+        prop_assert_eq!(rows.len(), n as usize, "one row per cottage");
+
+        let listed: Vec<_> = rows
+            .iter()
+            .filter_map(|row| {
+                let start = row.find("<@")? + 2;
+                let end = start + row[start..].find('>')?;
+                Some(UserId::new(row[start..end].parse().unwrap()))
+            })
+            .collect();
+        let expected = voting_order(&players, n, nominee);
+        prop_assert_eq!(listed.last(), Some(&vote.nominee), "the nominee comes last");
+        prop_assert_eq!(&listed, &expected);
+    }
+
+    #[test]
+    fn clock_hand_visits_everyone_once((n, players, nominee) in table()) {
+        let mut state = open_vote(&players, n, nominee);
+
+        let mut voters = vec![];
+        let mut complete = false;
+        for _ in 0..players.len() {
+            prop_assert!(!complete, "the vote finished early");
+            for event in engine::cast_vote(&mut state, true).unwrap() {
+                match event {
+                    GameEvent::Voted { voter, .. } => voters.push(voter),
+                    GameEvent::VoteComplete => complete = true,
+                    _ => (),
+                }
+            }
+        }
+
+        prop_assert!(complete);
+        prop_assert_eq!(voters, voting_order(&players, n, nominee));
+    }
+}
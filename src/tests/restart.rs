@@ -0,0 +1,110 @@
+use tokio::sync::RwLock;
+
+use super::{TOWN_SQUARE, VOTE_BUTTONS, config, player, seated_game};
+use crate::{
+    game::{Game, Nomination},
+    gateway::fake::FakeGateway,
+    state::{MessageStyle, State, VoteKind},
+};
+
+/// Saves and reloads the state the way a restart does
+async fn reload(state: &RwLock<State>) -> RwLock<State> {
+    let saved = serde_yml::to_string(&*state.read().await).unwrap();
+    RwLock::new(serde_yml::from_str(&saved).unwrap())
+}
+
+async fn start_vote(game: &Game<'_, FakeGateway>) {
+    game.start_vote(
+        TOWN_SQUARE,
+        Nomination {
+            nominator: player(1),
+            nominee: player(2),
+            description: "5 to execute".to_owned(),
+            kind: VoteKind::Execution,
+            secret: false,
+            thread: false,
+        },
+    )
+    .await
+    .unwrap();
+    game.set_hand(player(3), true).await.unwrap();
+}
+
+#[tokio::test]
+async fn vote_message_is_rerendered() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    start_vote(&game).await;
+    let message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+    let before = game.gateway.message(message_id).unwrap();
+
+    let state = reload(&state).await;
+    let game = Game {
+        gateway: game.gateway,
+        config: &config,
+        state: &state,
+    };
+    game.restore_vote().await.unwrap();
+
+    let after = game.gateway.message(message_id).unwrap();
+    assert_eq!(
+        state.read().await.current_vote.as_ref().unwrap().message_id,
+        message_id
+    );
+    assert_eq!(before.embed, after.embed);
+    assert_eq!(after.buttons, VOTE_BUTTONS);
+    // Nothing new in the town square, only player 3 was pinged in their cottage
+    let messages = game.gateway.messages.lock().unwrap().clone();
+    assert_eq!(messages.len(), 2);
+    let cottage = state
+        .read()
+        .await
+        .players
+        .values()
+        .find(|i| i.0 == player(3))
+        .unwrap()
+        .1;
+    assert_eq!(messages[1].1.channel_id, cottage);
+}
+
+#[tokio::test]
+async fn deleted_vote_message_is_reposted() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    start_vote(&game).await;
+    let old_message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+    game.gateway.delete_message(old_message_id);
+
+    let state = reload(&state).await;
+    let game = Game {
+        gateway: game.gateway,
+        config: &config,
+        state: &state,
+    };
+    game.restore_vote().await.unwrap();
+
+    let message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+    assert_ne!(message_id, old_message_id);
+    let message = game.gateway.message(message_id).unwrap();
+    assert_eq!(message.channel_id, TOWN_SQUARE);
+    assert_eq!(message.buttons, VOTE_BUTTONS);
+    assert!(message.content.contains("5 to execute"));
+    assert!(message.content.contains("<@3> 🙋"));
+
+    // Players can keep voting on the new message
+    game.set_hand(player(1), true).await.unwrap();
+    let message = game.gateway.message(message_id).unwrap();
+    assert!(message.content.contains("<@1> 🙋"));
+}
+
+#[tokio::test]
+async fn nothing_to_restore_without_a_vote() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+
+    game.restore_vote().await.unwrap();
+    assert!(game.gateway.messages.lock().unwrap().is_empty());
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use poise::serenity_prelude::UserId;
+
+use super::player;
+use crate::{
+    rules::{GameOver, LifeEvent, Team, check_game_over},
+    state::{Character, CharacterType, State},
+};
+
+/// Player 1 is the Imp, 2 the Saint, 3 the Mayor, 4 a Poisoner and everyone after a Chef
+fn characters(n: u64) -> HashMap<UserId, Character> {
+    (1..=n)
+        .map(|i| {
+            let (name, character_type) = match i {
+                1 => ("Imp", CharacterType::Demon),
+                2 => ("Saint", CharacterType::Outsider),
+                3 => ("Mayor", CharacterType::Townsfolk),
+                4 => ("Poisoner", CharacterType::Minion),
+                _ => ("Chef", CharacterType::Townsfolk),
+            };
+            (
+                player(i),
+                Character {
+                    name: name.to_owned(),
+                    character_type,
+                },
+            )
+        })
+        .collect()
+}
+
+/// A game with the characters from [`characters`]
+fn game(n: u64) -> State {
+    State {
+        characters: characters(n),
+        ..State::default()
+    }
+}
+
+fn alive(players: &[u64]) -> Vec<UserId> {
+    players.iter().map(|i| player(*i)).collect()
+}
+
+#[test]
+fn the_game_goes_on_while_the_demon_lives() {
+    let state = game(6);
+    let result = check_game_over(&state, &alive(&[1, 2, 3, 4, 5]), LifeEvent::Died);
+    assert_eq!(result, None);
+}
+
+#[test]
+fn good_wins_when_the_demon_dies() {
+    let state = game(6);
+    let result = check_game_over(
+        &state,
+        &alive(&[2, 3, 4, 5, 6]),
+        LifeEvent::Executed(player(1)),
+    );
+    assert_eq!(result, Some(GameOver::DemonDied));
+    assert_eq!(result.unwrap().winner(), Team::Good);
+}
+
+#[test]
+fn evil_wins_with_two_players_left() {
+    let state = game(6);
+    let result = check_game_over(&state, &alive(&[1, 5]), LifeEvent::Died);
+    assert_eq!(result, Some(GameOver::TwoPlayersLeft));
+    assert_eq!(result.unwrap().winner(), Team::Evil);
+}
+
+#[test]
+fn travellers_dont_keep_the_game_going() {
+    let mut state = game(6);
+    state.characters.insert(
+        player(7),
+        Character {
+            name: "Gunslinger".to_owned(),
+            character_type: CharacterType::Traveller,
+        },
+    );
+    let result = check_game_over(&state, &alive(&[1, 5, 7]), LifeEvent::Died);
+    assert_eq!(result, Some(GameOver::TwoPlayersLeft));
+
+    // Travellers seated with /traveller don't count either
+    let mut state = game(6);
+    state.travellers.insert(player(7));
+    let result = check_game_over(&state, &alive(&[1, 5, 7]), LifeEvent::Died);
+    assert_eq!(result, Some(GameOver::TwoPlayersLeft));
+    let result = check_game_over(&state, &alive(&[1, 3, 5, 7]), LifeEvent::NoExecution);
+    assert_eq!(result, Some(GameOver::MayorWin));
+}
+
+#[test]
+fn evil_wins_when_the_saint_is_executed() {
+    let state = game(6);
+    let result = check_game_over(
+        &state,
+        &alive(&[1, 3, 4, 5, 6]),
+        LifeEvent::Executed(player(2)),
+    );
+    assert_eq!(result, Some(GameOver::SaintExecuted));
+    assert_eq!(result.unwrap().winner(), Team::Evil);
+
+    // A Saint dying any other way changes nothing
+    let result = check_game_over(&state, &alive(&[1, 3, 4, 5, 6]), LifeEvent::Died);
+    assert_eq!(result, None);
+}
+
+#[test]
+fn good_wins_with_the_mayor_and_no_execution() {
+    let state = game(6);
+    let result = check_game_over(&state, &alive(&[1, 3, 5]), LifeEvent::NoExecution);
+    assert_eq!(result, Some(GameOver::MayorWin));
+    assert_eq!(result.unwrap().winner(), Team::Good);
+
+    // Only when nobody was executed
+    let result = check_game_over(&state, &alive(&[1, 3, 5]), LifeEvent::Died);
+    assert_eq!(result, None);
+}
+
+#[test]
+fn nothing_is_decided_without_a_known_demon() {
+    let result = check_game_over(&State::default(), &alive(&[1]), LifeEvent::Died);
+    assert_eq!(result, None);
+}
@@ -0,0 +1,302 @@
+//! Runs the scripted games in `tests/scenarios` against the engine and compares the rendered
+//! votes with the golden files next to them. Run with `UPDATE_GOLDEN=1` to rewrite the golden
+//! files after an intended change to the rendering.
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use poise::serenity_prelude::{ChannelId, UserId};
+use serde::Deserialize;
+
+use super::vote;
+use crate::{
+    engine,
+    state::{DeadState, State, Vote, VoteKind, format_vote},
+};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+    number_of_players: u32,
+    steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+struct Step {
+    #[serde(flatten)]
+    action: Action,
+    /// The action is expected to be refused with this message
+    #[serde(default)]
+    refused: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Action {
+    Seat {
+        cottage: u32,
+        player: u64,
+        #[serde(default)]
+        traveller: bool,
+    },
+    Die {
+        player: u64,
+        #[serde(default = "default_true")]
+        ghost_vote: bool,
+    },
+    Nominate {
+        nominator: u64,
+        nominee: u64,
+        #[serde(default)]
+        exile: bool,
+        #[serde(default)]
+        secret: bool,
+    },
+    RaiseHand {
+        player: u64,
+        #[serde(default = "default_true")]
+        raised: bool,
+    },
+    /// Votes for whoever the clock hand points at, one vote per entry
+    Vote(Vec<bool>),
+    Expect(Expectation),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct Expectation {
+    votes: Option<u32>,
+    threshold: Option<u32>,
+    /// The player the clock hand points at
+    clock_hand: Option<u64>,
+    /// Name of the golden file holding the expected `format_vote` output
+    render: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn user(player: u64) -> UserId {
+    UserId::new(player)
+}
+
+struct Runner {
+    name: String,
+    state: State,
+    /// Dead players carry over into every vote started after they die
+    dead: HashMap<UserId, DeadState>,
+    failures: Vec<String>,
+}
+
+impl Runner {
+    fn apply(&mut self, action: &Action) -> Result<(), engine::GameError> {
+        match *action {
+            Action::Seat {
+                cottage,
+                player,
+                traveller,
+            } => {
+                let cottage = engine::cottage(&self.state, cottage)?;
+                self.state
+                    .players
+                    .insert(cottage, (user(player), ChannelId::new(player)));
+                if traveller {
+                    self.state.travellers.insert(user(player));
+                }
+            }
+            Action::Die { player, ghost_vote } => {
+                self.dead.insert(
+                    user(player),
+                    if ghost_vote {
+                        DeadState::DeadVoteAvailable
+                    } else {
+                        DeadState::DeadVoteUsed
+                    },
+                );
+            }
+            Action::Nominate {
+                nominator,
+                nominee,
+                exile,
+                secret,
+            } => {
+                let kind = if exile {
+                    VoteKind::Exile
+                } else {
+                    VoteKind::Execution
+                };
+                let clock_hand = engine::check_nomination(&self.state, user(nominee), kind)?;
+                let vote = Vote {
+                    kind,
+                    clock_hand,
+                    dead_state: self.dead.clone(),
+                    secret,
+                    ..vote(nominator, nominee, &[])
+                };
+                engine::open_vote(&mut self.state, vote);
+            }
+            Action::RaiseHand { player, raised } => {
+                engine::set_hand(&mut self.state, user(player), raised)?;
+            }
+            Action::Vote(ref votes) => {
+                for yes in votes {
+                    for event in engine::cast_vote(&mut self.state, *yes)? {
+                        if let engine::GameEvent::GhostVoteUsed { voter } = event {
+                            self.dead.insert(voter, DeadState::DeadVoteUsed);
+                        }
+                    }
+                }
+            }
+            Action::Expect(ref expectation) => self.check(expectation),
+        }
+        Ok(())
+    }
+
+    fn check(&mut self, expectation: &Expectation) {
+        let Some(vote) = &self.state.current_vote else {
+            self.failures
+                .push("expected a vote, but none was started".to_owned());
+            return;
+        };
+
+        let votes = self.state.vote_modifiers().tally(vote).votes;
+        if let Some(expected) = expectation.votes
+            && expected != votes
+        {
+            self.failures
+                .push(format!("expected {expected} votes, got {votes}"));
+        }
+
+        let threshold = vote.threshold(&self.state.players);
+        if let Some(expected) = expectation.threshold
+            && expected != threshold
+        {
+            self.failures.push(format!(
+                "expected a threshold of {expected}, got {threshold}"
+            ));
+        }
+
+        let clock_hand = self.state.players.get(&vote.clock_hand).map(|i| i.0);
+        if let Some(expected) = expectation.clock_hand
+            && Some(user(expected)) != clock_hand
+        {
+            self.failures.push(format!(
+                "expected the clock hand on {expected}, got {clock_hand:?}"
+            ));
+        }
+
+        if let Some(render) = &expectation.render {
+            let actual = format_vote(&self.state.players, vote, self.state.number_of_players);
+            let path = golden_dir().join(&self.name).join(format!("{render}.txt"));
+            if let Some(failure) = compare_golden(&path, &actual) {
+                self.failures.push(failure);
+            }
+        }
+    }
+}
+
+fn scenario_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenarios")
+}
+
+fn golden_dir() -> PathBuf {
+    scenario_dir().join("golden")
+}
+
+/// Compares a render with its golden file, or writes the golden file when updating
+fn compare_golden(path: &Path, actual: &str) -> Option<String> {
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent()?).unwrap();
+        fs::write(path, actual).unwrap();
+        return None;
+    }
+
+    let Ok(expected) = fs::read_to_string(path) else {
+        return Some(format!(
+            "missing golden file {}, run with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        ));
+    };
+    if expected == actual {
+        return None;
+    }
+
+    let mut diff = format!("{} differs:\n", path.display());
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => {
+                let _ = writeln!(diff, "  {e}");
+            }
+            (e, a) => {
+                if let Some(e) = e {
+                    let _ = writeln!(diff, "- {e}");
+                }
+                if let Some(a) = a {
+                    let _ = writeln!(diff, "+ {a}");
+                }
+            }
+        }
+    }
+    Some(diff)
+}
+
+fn run(path: &Path) -> Vec<String> {
+    let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+    let scenario: Scenario = match serde_yml::from_str(&fs::read_to_string(path).unwrap()) {
+        Ok(scenario) => scenario,
+        Err(e) => return vec![format!("{name}: couldn't parse the script: {e}")],
+    };
+
+    let mut runner = Runner {
+        name: name.clone(),
+        state: State {
+            number_of_players: scenario.number_of_players,
+            ..Default::default()
+        },
+        dead: HashMap::new(),
+        failures: vec![],
+    };
+    for (i, step) in scenario.steps.iter().enumerate() {
+        let result = runner.apply(&step.action);
+        let failure = match (result, &step.refused) {
+            (Ok(()), None) => None,
+            (Err(e), Some(expected)) if e.to_string() == *expected => None,
+            (Err(e), Some(expected)) => Some(format!(
+                "expected \"{expected}\" to be refused, got \"{e}\""
+            )),
+            (Err(e), None) => Some(format!("refused: {e}")),
+            (Ok(()), Some(expected)) => Some(format!("expected \"{expected}\" to be refused")),
+        };
+        runner.failures.extend(failure);
+        if !runner.failures.is_empty() {
+            return runner
+                .failures
+                .iter()
+                .map(|failure| format!("{name} step {}: {failure}", i + 1))
+                .collect();
+        }
+    }
+
+    vec![]
+}
+
+#[test]
+fn scenarios() {
+    let mut paths: Vec<_> = fs::read_dir(scenario_dir())
+        .unwrap()
+        .map(|i| i.unwrap().path())
+        .filter(|i| i.extension().is_some_and(|i| i == "yaml"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scenarios found");
+
+    let failures: Vec<_> = paths.iter().flat_map(|i| run(i)).collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,176 @@
+//! Reseating players between and during votes
+
+use poise::serenity_prelude::ChannelId;
+use rand::{SeedableRng, rngs::StdRng};
+
+use super::{player, players, vote};
+use crate::{
+    engine::GameError,
+    state::{
+        Character, CharacterType, CottageNumber, DeadState, State, VoteRecord, VoteState, Whisper,
+    },
+};
+
+fn cottage(n: u32) -> CottageNumber {
+    CottageNumber::new(n).unwrap()
+}
+
+/// Five players in the cottages with the same number
+fn five_players() -> State {
+    State {
+        number_of_players: 5,
+        players: players(5),
+        ..State::default()
+    }
+}
+
+/// Who sits in each cottage, by player number, 0 for an empty cottage
+fn seated(state: &State) -> Vec<u64> {
+    state
+        .seating()
+        .into_iter()
+        .map(|seat| seat.map_or(0, |(user_id, _)| user_id.get()))
+        .collect()
+}
+
+#[test]
+fn swapping_exchanges_two_cottages() {
+    let mut state = five_players();
+    state.players.remove(&cottage(4));
+
+    state.swap_seats(cottage(1), cottage(3)).unwrap();
+    state.swap_seats(cottage(2), cottage(4)).unwrap();
+
+    assert_eq!(seated(&state), [3, 0, 1, 2, 5]);
+    // Players take their cottage channel with them
+    assert_eq!(state.players[&cottage(1)].1, ChannelId::new(3));
+}
+
+#[test]
+fn moving_shifts_everyone_in_between() {
+    let mut state = five_players();
+
+    state
+        .move_player((player(1), ChannelId::new(1)), cottage(4))
+        .unwrap();
+    assert_eq!(seated(&state), [2, 3, 4, 1, 5]);
+
+    state
+        .move_player((player(5), ChannelId::new(5)), cottage(1))
+        .unwrap();
+    assert_eq!(seated(&state), [5, 2, 3, 4, 1]);
+}
+
+#[test]
+fn moving_a_new_player_adds_a_cottage() {
+    let mut state = five_players();
+
+    state
+        .move_player((player(6), ChannelId::new(6)), cottage(3))
+        .unwrap();
+
+    assert_eq!(state.number_of_players, 6);
+    assert_eq!(seated(&state), [1, 2, 6, 3, 4, 5]);
+}
+
+#[test]
+fn shuffling_keeps_everyone_seated() {
+    let mut state = five_players();
+    state.players.remove(&cottage(2));
+
+    state.shuffle_seats(&mut StdRng::seed_from_u64(7)).unwrap();
+
+    let mut seated = seated(&state);
+    assert_eq!(state.number_of_players, 5);
+    seated.sort();
+    assert_eq!(seated, [0, 1, 3, 4, 5]);
+}
+
+#[test]
+fn seats_are_locked_while_the_clock_hand_goes_round() {
+    let mut state = five_players();
+    state.current_vote = Some(vote(1, 2, &[]));
+
+    assert_eq!(
+        state.swap_seats(cottage(1), cottage(3)),
+        Err(GameError::VoteInProgress)
+    );
+    assert_eq!(
+        state.move_player((player(1), ChannelId::new(1)), cottage(4)),
+        Err(GameError::VoteInProgress)
+    );
+    assert_eq!(
+        state.shuffle_seats(&mut StdRng::seed_from_u64(7)),
+        Err(GameError::VoteInProgress)
+    );
+    assert_eq!(seated(&state), [1, 2, 3, 4, 5]);
+
+    // Once everyone has voted the table can change again
+    let vote = state.current_vote.as_mut().unwrap();
+    for n in 1..=5 {
+        vote.vote_state.insert(player(n), VoteState::No);
+    }
+    state.swap_seats(cottage(1), cottage(3)).unwrap();
+    assert_eq!(seated(&state), [3, 2, 1, 4, 5]);
+}
+
+#[test]
+fn substitutes_take_over_everything_tied_to_the_seat() {
+    let mut state = five_players();
+    state.characters.insert(
+        player(2),
+        Character {
+            name: "Beggar".to_owned(),
+            character_type: CharacterType::Traveller,
+        },
+    );
+    state.travellers.insert(player(2));
+    state.vote_history.push(VoteRecord {
+        nominator: player(2),
+        nominee: player(3),
+        votes: 3,
+        threshold: 3,
+    });
+    state.vote_history.push(VoteRecord {
+        nominator: player(4),
+        nominee: player(2),
+        votes: 1,
+        threshold: 3,
+    });
+    state.whispers.threads.push(Whisper {
+        players: [player(1), player(2)],
+        thread_id: ChannelId::new(50),
+        started: vec![],
+        messages: 2,
+    });
+    let mut current = vote(2, 4, &[(2, DeadState::DeadVoteUsed)]);
+    current.vote_state.insert(player(2), VoteState::Yes);
+    current.spent_ghost_votes.insert(player(2));
+    state.current_vote = Some(current);
+
+    let seat = state.substitute(player(2), player(6));
+
+    assert_eq!(seat, Some((cottage(2), ChannelId::new(2))));
+    assert_eq!(seated(&state), [1, 6, 3, 4, 5]);
+    assert!(state.characters.contains_key(&player(6)));
+    assert!(state.travellers.contains(&player(6)));
+    let history: Vec<_> = state
+        .vote_history
+        .iter()
+        .map(|i| (i.nominator, i.nominee))
+        .collect();
+    assert_eq!(history, [(player(6), player(3)), (player(4), player(6))]);
+    assert_eq!(state.whispers.threads[0].players, [player(1), player(6)]);
+    let current = state.current_vote.as_ref().unwrap();
+    assert_eq!(current.nominator, player(6));
+    assert!(matches!(
+        current.vote_state.get(&player(6)),
+        Some(VoteState::Yes)
+    ));
+    assert!(matches!(
+        current.dead_state.get(&player(6)),
+        Some(DeadState::DeadVoteUsed)
+    ));
+    assert!(current.spent_ghost_votes.contains(&player(6)));
+    assert!(!current.spent_ghost_votes.contains(&player(2)));
+}
@@ -0,0 +1,314 @@
+//! Whispers, sign-ups, substitutes, cottages and transcripts, played against [`FakeGateway`]
+
+use botc_discord_bot::{message_log::LogEvent, transcript::TranscriptFormat};
+use poise::serenity_prelude::{MessageId, PermissionOverwriteType, Permissions, User};
+use tokio::sync::RwLock;
+
+use super::{DEAD, GHOST_VOTE, TOWN_SQUARE, config, player, seated_game};
+use crate::{
+    Error,
+    engine::GameError,
+    game::{COTTAGE_ACCESS, CottageTeardown, SeatingOrder},
+    gateway::{Gateway, fake::FakeGateway},
+    log_writer::LogWriter,
+    rules::GameOver,
+    state::{CottageNumber, MessageStyle, Phase, State},
+};
+
+#[tokio::test]
+async fn whispers_reuse_their_private_thread() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+
+    let thread_id = game
+        .whisper(TOWN_SQUARE, player(1), player(2))
+        .await
+        .unwrap();
+    game.gateway.archive_thread(thread_id).await.unwrap();
+    let again = game
+        .whisper(TOWN_SQUARE, player(2), player(1))
+        .await
+        .unwrap();
+
+    assert_eq!(again, thread_id);
+    let thread = game.gateway.private_threads.lock().unwrap()[&thread_id].clone();
+    assert_eq!(thread.name, "Whisper: Player 1 & Player 2");
+    assert_eq!(thread.members, [player(1), player(2)].into());
+    assert!(!thread.archived);
+    let whispers = &state.read().await.whispers.threads;
+    assert_eq!(whispers.len(), 1);
+    assert_eq!(whispers[0].started.len(), 2);
+    // The storytellers are mentioned in an edit, which doesn't ping them
+    let messages = game.gateway.messages.lock().unwrap();
+    let (_, intro) = messages
+        .iter()
+        .find(|(_, i)| i.channel_id == thread_id)
+        .unwrap();
+    assert_eq!(
+        intro.content,
+        "<@1> is whispering to <@2>, <@&1> can read along"
+    );
+}
+
+#[tokio::test]
+async fn whispers_need_two_seated_players() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 2).await;
+
+    let refusals = [(1, 3), (3, 1), (1, 1)];
+    for (author, player_id) in refusals {
+        let result = game
+            .whisper(TOWN_SQUARE, player(author), player(player_id))
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::Validation(_) | Error::Game(GameError::NotSeated(_)))
+        ));
+    }
+
+    state.write().await.phase = Phase::Night;
+    state.write().await.whispers.close_at_night = true;
+    let result = game.whisper(TOWN_SQUARE, player(1), player(2)).await;
+    assert!(matches!(result, Err(Error::Validation(_))));
+    assert!(game.gateway.private_threads.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn whispers_lock_for_the_night() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    let first = game
+        .whisper(TOWN_SQUARE, player(1), player(2))
+        .await
+        .unwrap();
+    let second = game
+        .whisper(TOWN_SQUARE, player(3), player(1))
+        .await
+        .unwrap();
+    let thread = |thread_id| game.gateway.private_threads.lock().unwrap()[&thread_id].clone();
+
+    game.lock_whispers(true).await.unwrap();
+    for thread_id in [first, second] {
+        assert!(thread(thread_id).locked);
+        assert!(thread(thread_id).archived);
+    }
+
+    game.lock_whispers(false).await.unwrap();
+    for thread_id in [first, second] {
+        assert!(!thread(thread_id).locked);
+        assert!(!thread(thread_id).archived);
+    }
+}
+
+/// The permissions a member has on a channel the fake server created
+fn member_permissions(
+    gateway: &FakeGateway,
+    channel: usize,
+    user: u64,
+) -> Option<(Permissions, Permissions)> {
+    let channels = gateway.channels.lock().unwrap();
+    channels[channel]
+        .2
+        .iter()
+        .find(|i| i.kind == PermissionOverwriteType::Member(player(user)))
+        .map(|i| (i.allow, i.deny))
+}
+
+#[tokio::test]
+async fn archived_cottages_are_read_only() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 2, 2).await;
+
+    let (closed, failures) = game.close_cottages(CottageTeardown::Archive).await.unwrap();
+
+    assert_eq!(closed, 2);
+    assert!(failures.is_empty());
+    let (allow, deny) = member_permissions(&game.gateway, 0, 1).unwrap();
+    assert!(allow.contains(Permissions::VIEW_CHANNEL));
+    assert!(deny.contains(Permissions::SEND_MESSAGES));
+    let state = state.read().await;
+    assert!(state.provisioned_cottages.is_empty());
+    assert_eq!(state.players.len(), 2);
+}
+
+#[tokio::test]
+async fn cottages_that_fail_to_close_are_kept() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 2, 2).await;
+    // Someone deleted the first cottage by hand
+    let gone = game.gateway.channels.lock().unwrap().remove(0).0;
+
+    let (closed, failures) = game.close_cottages(CottageTeardown::Delete).await.unwrap();
+
+    assert_eq!(closed, 1);
+    assert_eq!(failures, [gone]);
+    assert!(game.gateway.channels.lock().unwrap().is_empty());
+    let state = state.read().await;
+    assert_eq!(state.provisioned_cottages, [gone].into());
+    assert_eq!(
+        state.players.values().map(|i| i.1).collect::<Vec<_>>(),
+        [gone]
+    );
+}
+
+#[tokio::test]
+async fn signups_fill_the_empty_cottages() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 4, 2).await;
+
+    game.open_signups(TOWN_SQUARE).await.unwrap();
+    state
+        .write()
+        .await
+        .signups
+        .queue
+        .extend([player(3), player(4), player(5)]);
+    game.seat_signups(SeatingOrder::JoinOrder).await.unwrap();
+
+    let (_, message_id) = state.read().await.signups.message.unwrap();
+    let message = game.gateway.message(message_id).unwrap();
+    assert_eq!(
+        message.buttons,
+        ["signup_join_button", "signup_leave_button"]
+    );
+    assert_eq!(
+        message.content,
+        "**Sign-ups** (4 seated, 0 of 0 open seats taken)\n**Waitlist**\n1. <@5>\n"
+    );
+    let state = state.read().await;
+    let cottage = |n| state.players[&CottageNumber::new(n).unwrap()].0;
+    assert_eq!([cottage(3), cottage(4)], [player(3), player(4)]);
+    assert_eq!(state.signups.queue, [player(5)]);
+}
+
+#[tokio::test]
+async fn players_join_and_leave_the_signups() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 4, 2).await;
+    game.open_signups(TOWN_SQUARE).await.unwrap();
+
+    for n in [3, 4, 3] {
+        game.join_signups(player(n)).await.unwrap();
+    }
+    let result = game.join_signups(player(1)).await;
+    assert!(matches!(result, Err(Error::Validation(_))));
+    assert_eq!(state.read().await.signups.queue, [player(3), player(4)]);
+
+    game.leave_signups(player(3)).await.unwrap();
+    assert_eq!(state.read().await.signups.queue, [player(4)]);
+    let (_, message_id) = state.read().await.signups.message.unwrap();
+    assert_eq!(
+        game.gateway.message(message_id).unwrap().content,
+        "**Sign-ups** (2 seated, 1 of 2 open seats taken)\n1. <@4>\n"
+    );
+}
+
+#[tokio::test]
+async fn substitutes_take_over_the_cottage_and_roles() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 2, 2).await;
+    game.mark_dead(player(2)).await.unwrap();
+    state.write().await.signups.queue.push(player(3));
+
+    let (new, cottage) = game.substitute(player(2), None).await.unwrap();
+
+    assert_eq!(new, player(3));
+    assert_eq!(cottage, CottageNumber::new(2).unwrap());
+    assert!(game.gateway.has_role(player(3), DEAD));
+    assert!(game.gateway.has_role(player(3), GHOST_VOTE));
+    assert!(!game.gateway.has_role(player(2), DEAD));
+    assert_eq!(
+        member_permissions(&game.gateway, 1, 3),
+        Some((COTTAGE_ACCESS, Permissions::empty()))
+    );
+    assert_eq!(member_permissions(&game.gateway, 1, 2), None);
+    assert!(state.read().await.signups.queue.is_empty());
+
+    let result = game.substitute(player(1), None).await;
+    assert!(matches!(result, Err(Error::Validation(_))));
+}
+
+#[tokio::test]
+async fn ending_the_game_announces_why() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 2, 2).await;
+
+    game.end_game(TOWN_SQUARE, GameOver::DemonDied)
+        .await
+        .unwrap();
+
+    let state = state.read().await;
+    assert_eq!(state.phase, Phase::GameOver);
+    assert!(state.timeline.ended.is_some());
+    let messages = game.gateway.messages.lock().unwrap();
+    let (_, announcement) = messages.last().unwrap();
+    assert_eq!(announcement.channel_id, TOWN_SQUARE);
+    assert!(announcement.content.starts_with("**Game Over!**"));
+}
+
+#[tokio::test]
+async fn transcripts_reveal_cottages_once_the_game_is_over() {
+    let dir = std::env::temp_dir().join(format!("botc-transcript-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut config = config(MessageStyle::PlainText);
+    config.log.path = dir.join("message_log.jsonl");
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 2, 2).await;
+    game.start_day().await.unwrap();
+    let cottage = state.read().await.players[&CottageNumber::new(1).unwrap()].1;
+
+    let writer = LogWriter::spawn(config.log.clone()).unwrap();
+    let log = [
+        (1, TOWN_SQUARE, "Good morning"),
+        (2, cottage, "I'm the Imp"),
+    ];
+    for (id, channel_id, content) in log {
+        let mut author = User::default();
+        author.name = "alice".to_owned();
+        writer
+            .write(LogEvent::NewMessage {
+                message_id: MessageId::new(id),
+                content: content.to_owned(),
+                author,
+                channel_id,
+                thread: None,
+                attachments: vec![],
+            })
+            .await;
+    }
+    writer.flush().await;
+
+    let hidden = game
+        .transcript(TranscriptFormat::Markdown, None, false)
+        .await
+        .unwrap();
+    assert!(hidden.contains("alice: Good morning"));
+    assert!(!hidden.contains("Imp"));
+    let result = game
+        .transcript(TranscriptFormat::Markdown, None, true)
+        .await;
+    assert!(matches!(result, Err(Error::Validation(_))));
+
+    game.end_game(TOWN_SQUARE, GameOver::DemonDied)
+        .await
+        .unwrap();
+    let revealed = game
+        .transcript(TranscriptFormat::Markdown, None, true)
+        .await
+        .unwrap();
+    let (_, reveals) = revealed.split_once("## Post-Game Reveals").unwrap();
+    // Channels are named after the server's channels
+    assert!(reveals.contains("### #cottage-Player 1"));
+    assert!(reveals.contains("alice: I'm the Imp"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
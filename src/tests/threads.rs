@@ -0,0 +1,249 @@
+use std::time::SystemTime;
+
+use poise::serenity_prelude::ChannelId;
+use tokio::sync::RwLock;
+
+use super::{TOWN_SQUARE, config, nomination, player, seated_game};
+use crate::{
+    Error,
+    engine::GameError,
+    game::{Game, Nomination, statement_button_id},
+    gateway::fake::{FakeGateway, SentMessage},
+    state::{MessageStyle, Phase, State, Statement, Whisper},
+};
+
+fn with_thread(nominator: u64, nominee: u64) -> Nomination {
+    Nomination {
+        thread: true,
+        ..nomination(nominator, nominee)
+    }
+}
+
+#[tokio::test]
+async fn threads_are_opened_on_the_vote_message() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+
+    game.start_vote(TOWN_SQUARE, with_thread(1, 2))
+        .await
+        .unwrap();
+
+    let state = state.read().await;
+    let vote = state.current_vote.as_ref().unwrap();
+    let threads = game.gateway.threads.lock().unwrap();
+    let (thread_id, name, archived) = &threads[&vote.message_id];
+    assert_eq!(Some(*thread_id), vote.thread.as_ref().map(|i| i.thread_id));
+    assert_eq!(name, "Nomination of Player 2");
+    assert!(!archived);
+}
+
+/// What the bot posted in the town square and the vote thread, leaving out clock hand pings
+fn public_messages(game: &Game<'_, FakeGateway>, thread_id: ChannelId) -> Vec<SentMessage> {
+    game.gateway
+        .messages
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, i)| [TOWN_SQUARE, thread_id].contains(&i.channel_id))
+        .map(|(_, i)| i.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn confirmed_statements_show_on_the_vote() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    game.start_vote(TOWN_SQUARE, with_thread(1, 2))
+        .await
+        .unwrap();
+    let (message_id, thread_id) = {
+        let state = state.read().await;
+        let vote = state.current_vote.as_ref().unwrap();
+        (vote.message_id, vote.thread.as_ref().unwrap().thread_id)
+    };
+
+    // Only the nominator and nominee are offered, and only in the thread
+    game.thread_message(thread_id, player(3), "They're the demon")
+        .await
+        .unwrap();
+    game.thread_message(TOWN_SQUARE, player(1), "They're the demon")
+        .await
+        .unwrap();
+    assert_eq!(public_messages(&game, thread_id).len(), 1);
+
+    game.thread_message(thread_id, player(1), "They're the demon")
+        .await
+        .unwrap();
+    // A second message doesn't replace the one waiting for the storyteller
+    game.thread_message(thread_id, player(1), "Also they lied")
+        .await
+        .unwrap();
+    let messages = public_messages(&game, thread_id);
+    assert_eq!(messages.len(), 2);
+    let prompt = &messages[1];
+    assert_eq!(prompt.channel_id, thread_id);
+    assert!(prompt.content.contains("> They're the demon"));
+    assert_eq!(
+        prompt.buttons,
+        [
+            statement_button_id(message_id, Statement::Accusation, true),
+            statement_button_id(message_id, Statement::Accusation, false)
+        ]
+    );
+
+    game.resolve_statement(message_id, Statement::Accusation, true)
+        .await
+        .unwrap();
+    assert_eq!(
+        state.read().await.current_vote.as_ref().unwrap().accusation,
+        "They're the demon"
+    );
+    let message = game.gateway.message(message_id).unwrap();
+    assert!(message.content.contains("> They're the demon"));
+
+    // Once set, further messages aren't offered
+    game.thread_message(thread_id, player(1), "And another thing")
+        .await
+        .unwrap();
+    assert_eq!(public_messages(&game, thread_id).len(), 2);
+}
+
+#[tokio::test]
+async fn dismissed_statements_make_way_for_the_next_message() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    game.start_vote(TOWN_SQUARE, with_thread(1, 2))
+        .await
+        .unwrap();
+    let (message_id, thread_id) = {
+        let state = state.read().await;
+        let vote = state.current_vote.as_ref().unwrap();
+        (vote.message_id, vote.thread.as_ref().unwrap().thread_id)
+    };
+
+    game.thread_message(thread_id, player(2), "hi")
+        .await
+        .unwrap();
+    game.resolve_statement(message_id, Statement::Defense, false)
+        .await
+        .unwrap();
+    assert!(matches!(
+        game.resolve_statement(message_id, Statement::Defense, true)
+            .await,
+        Err(Error::Validation(_))
+    ));
+
+    game.thread_message(thread_id, player(2), "I'm the Virgin")
+        .await
+        .unwrap();
+    game.resolve_statement(message_id, Statement::Defense, true)
+        .await
+        .unwrap();
+    {
+        let state = state.read().await;
+        let vote = state.current_vote.as_ref().unwrap();
+        assert_eq!(vote.defense, "I'm the Virgin");
+        assert!(vote.accusation.is_empty());
+    }
+
+    // A prompt left over from this nomination doesn't touch the next one
+    game.start_vote(TOWN_SQUARE, with_thread(1, 3))
+        .await
+        .unwrap();
+    let next_thread = state
+        .read()
+        .await
+        .current_vote
+        .as_ref()
+        .unwrap()
+        .thread
+        .as_ref()
+        .unwrap()
+        .thread_id;
+    game.thread_message(next_thread, player(3), "I'm the Soldier")
+        .await
+        .unwrap();
+    let result = game
+        .resolve_statement(message_id, Statement::Defense, true)
+        .await;
+    assert!(matches!(result, Err(Error::Game(GameError::VoteClosed))));
+    let state = state.read().await;
+    let vote = state.current_vote.as_ref().unwrap();
+    assert!(vote.defense.is_empty());
+    assert!(vote.thread.as_ref().unwrap().pending_defense.is_some());
+}
+
+#[tokio::test]
+async fn threads_are_archived_when_the_vote_ends() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    game.start_vote(TOWN_SQUARE, with_thread(1, 2))
+        .await
+        .unwrap();
+    let message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+
+    for _ in 0..2 {
+        game.cast_vote(true).await.unwrap();
+    }
+    assert!(!game.gateway.threads.lock().unwrap()[&message_id].2);
+    game.cast_vote(true).await.unwrap();
+    assert!(game.gateway.threads.lock().unwrap()[&message_id].2);
+
+    // A vote that never finished is archived when the next one starts
+    game.start_vote(TOWN_SQUARE, with_thread(2, 3))
+        .await
+        .unwrap();
+    let message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+    game.start_vote(TOWN_SQUARE, nomination(3, 1))
+        .await
+        .unwrap();
+    assert!(game.gateway.threads.lock().unwrap()[&message_id].2);
+}
+
+#[tokio::test]
+async fn whisper_messages_are_counted() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    let thread_id = ChannelId::new(50);
+    state.write().await.whispers.threads.push(Whisper {
+        players: [player(1), player(2)],
+        thread_id,
+        started: vec![SystemTime::now()],
+        messages: 0,
+    });
+
+    game.whisper_message(thread_id, player(1)).await.unwrap();
+    game.whisper_message(thread_id, player(2)).await.unwrap();
+    game.whisper_message(thread_id, player(2)).await.unwrap();
+    // Storytellers reading along and messages elsewhere don't count
+    game.whisper_message(thread_id, player(3)).await.unwrap();
+    game.whisper_message(TOWN_SQUARE, player(1)).await.unwrap();
+
+    assert_eq!(state.read().await.whispers.threads[0].messages, 3);
+}
+
+#[tokio::test]
+async fn whispers_are_forgotten_when_a_new_game_starts() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    {
+        let mut state = state.write().await;
+        state.phase = Phase::GameOver;
+        state.whispers.threads.push(Whisper {
+            players: [player(1), player(2)],
+            thread_id: ChannelId::new(50),
+            started: vec![SystemTime::now()],
+            messages: 4,
+        });
+    }
+
+    game.start_day().await.unwrap();
+
+    assert!(state.read().await.whispers.threads.is_empty());
+}
@@ -0,0 +1,175 @@
+//! Transcripts rendered from a small log, compared against the exact output
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use botc_discord_bot::{
+    message_log::{LogEvent, LogRecord},
+    transcript::{GameTimeline, TranscriptFormat, TranscriptOptions, build_transcript},
+};
+use poise::serenity_prelude::{ChannelId, MessageId, User, UserId};
+
+const TOWN_SQUARE: ChannelId = ChannelId::new(10);
+const COTTAGE: ChannelId = ChannelId::new(20);
+
+/// Minutes after the first day started, which is at 12:00 UTC
+fn at(minutes: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(12 * 3600 + minutes * 60)
+}
+
+fn message(minutes: u64, id: u64, author: &str, channel_id: ChannelId, content: &str) -> LogRecord {
+    let mut user = User::default();
+    user.name = author.to_owned();
+    LogRecord {
+        version: 1,
+        time: at(minutes),
+        event: LogEvent::NewMessage {
+            message_id: MessageId::new(id),
+            content: content.to_owned(),
+            author: user,
+            channel_id,
+            thread: None,
+            attachments: vec![],
+        },
+    }
+}
+
+fn records() -> Vec<LogRecord> {
+    vec![
+        message(1, 1, "alice", TOWN_SQUARE, "Good morning"),
+        message(
+            2,
+            2,
+            "bob",
+            TOWN_SQUARE,
+            "I'm the *Chef*\n\n# and I got a 1",
+        ),
+        message(3, 3, "carol", TOWN_SQUARE, "<b>typo</b> & more"),
+        LogRecord {
+            version: 1,
+            time: at(4),
+            event: LogEvent::MessageEdit {
+                message_id: MessageId::new(3),
+                content: Some("<b>fixed</b> & more".to_owned()),
+                author: None,
+                channel_id: TOWN_SQUARE,
+                reactions: None,
+            },
+        },
+        message(5, 4, "alice", TOWN_SQUARE, "Nothing to see here"),
+        LogRecord {
+            version: 1,
+            time: at(6),
+            event: LogEvent::MessageDelete {
+                channel_id: TOWN_SQUARE,
+                message_id: MessageId::new(4),
+                guild_id: None,
+            },
+        },
+        message(7, 5, "bob", COTTAGE, "I lied, I'm the Imp"),
+        message(24 * 60 + 1, 6, "carol", TOWN_SQUARE, "1. Execute bob"),
+    ]
+}
+
+fn options(reveal_cottages: bool) -> TranscriptOptions {
+    TranscriptOptions {
+        timeline: GameTimeline {
+            days: vec![at(0), at(24 * 60)],
+            ended: Some(at(2 * 24 * 60)),
+        },
+        channels: HashSet::new(),
+        cottage_channels: HashSet::from([COTTAGE]),
+        reveal_cottages,
+        channel_names: HashMap::from([
+            (TOWN_SQUARE, "town-square".to_owned()),
+            (COTTAGE, "cottage-2".to_owned()),
+        ]),
+    }
+}
+
+#[test]
+fn markdown_transcript() {
+    let transcript = build_transcript(records(), options(true));
+
+    assert_eq!(
+        transcript.render(TranscriptFormat::Markdown),
+        // Every message line ends in two spaces, a line break in Markdown
+        concat!(
+            "# Game Transcript\n",
+            "\n",
+            "## Day 1\n",
+            "\n",
+            "### #town-square\n",
+            "\n",
+            "**12:01** alice: Good morning  \n",
+            "**12:02** bob: I'm the \\*Chef\\*  \n",
+            "\\# and I got a 1  \n",
+            "**12:03** carol: \\<b\\>fixed\\</b\\> & more *(edited)*  \n",
+            "\n",
+            "## Day 2\n",
+            "\n",
+            "### #town-square\n",
+            "\n",
+            "**12:01** carol: 1\\. Execute bob  \n",
+            "\n",
+            "## Post-Game Reveals\n",
+            "\n",
+            "### #cottage-2\n",
+            "\n",
+            "**12:07** bob: I lied, I'm the Imp  \n",
+        )
+    );
+}
+
+#[test]
+fn html_transcript() {
+    let transcript = build_transcript(records(), options(false));
+
+    assert_eq!(
+        transcript.render(TranscriptFormat::Html),
+        "<!DOCTYPE html>
+<html>
+<head><meta charset=\"utf-8\"><title>Game Transcript</title></head>
+<body>
+<h1>Game Transcript</h1>
+<h2>Day 1</h2>
+<h3>#town-square</h3>
+<p><time>12:01</time> <b>alice</b>: Good morning</p>
+<p><time>12:02</time> <b>bob</b>: I'm the *Chef*<br><br># and I got a 1</p>
+<p><time>12:03</time> <b>carol</b>: &lt;b&gt;fixed&lt;/b&gt; &amp; more <i>(edited)</i></p>
+<h2>Day 2</h2>
+<h3>#town-square</h3>
+<p><time>12:01</time> <b>carol</b>: 1. Execute bob</p>
+</body>
+</html>
+"
+    );
+}
+
+#[test]
+fn cottages_seated_earlier_stay_private() {
+    // Player 3 sat in their own cottage on day 1 and was moved out of it before the export
+    let old_cottage = ChannelId::new(30);
+    let mut records = records();
+    records.insert(
+        0,
+        LogRecord {
+            version: 1,
+            time: at(0),
+            event: LogEvent::Seating {
+                cottages: vec![(3, UserId::new(3), old_cottage)],
+            },
+        },
+    );
+    records.push(message(8, 7, "dave", old_cottage, "I'm the Spy"));
+
+    let hidden =
+        build_transcript(records.clone(), options(false)).render(TranscriptFormat::Markdown);
+    assert!(!hidden.contains("Spy"));
+
+    let revealed = build_transcript(records, options(true)).render(TranscriptFormat::Markdown);
+    let (_, reveals) = revealed.split_once("## Post-Game Reveals").unwrap();
+    assert!(reveals.contains("**12:08** dave: I'm the Spy"));
+}
@@ -0,0 +1,713 @@
+use std::time::{Duration, SystemTime};
+
+use poise::serenity_prelude::{Colour, PermissionOverwriteType};
+use tokio::sync::RwLock;
+
+use super::{
+    DEAD, GHOST_VOTE, STORYTELLER, TOWN_SQUARE, VOTE_BUTTONS, config, nomination, player,
+    seated_game,
+};
+use crate::{
+    Error,
+    deadlines::Deadline,
+    engine::{GameError, GameEvent},
+    game::{COTTAGE_ACCESS, MAX_STATEMENT_LENGTH, Nomination},
+    gateway::Gateway,
+    modifiers::ReminderToken,
+    state::{Character, CharacterType, MessageStyle, Phase, State, Statement, VoteKind},
+};
+
+#[tokio::test]
+async fn seating_creates_private_cottages() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+
+    let channels = game.gateway.channels.lock().unwrap().clone();
+    assert_eq!(channels.len(), 3);
+    let state = state.read().await;
+    for (cottage, (user_id, channel_id)) in &state.players {
+        let (_, name, permissions) = channels.iter().find(|i| i.0 == *channel_id).unwrap();
+        assert_eq!(*name, format!("cottage-Player {user_id}"));
+        assert!(
+            permissions.iter().any(|i| i.allow == COTTAGE_ACCESS
+                && i.kind == PermissionOverwriteType::Member(*user_id))
+        );
+        assert!(
+            permissions.iter().any(|i| i.allow == COTTAGE_ACCESS
+                && i.kind == PermissionOverwriteType::Role(STORYTELLER))
+        );
+        assert_eq!(cottage.0.get() as u64, user_id.get());
+    }
+    assert_eq!(state.provisioned_cottages.len(), 3);
+}
+
+#[tokio::test]
+async fn seating_refuses_missing_cottages() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 0).await;
+
+    let result = game.seat_player(4, player(4), None, None).await;
+    assert!(matches!(
+        result,
+        Err(Error::Validation(message)) if message == "There is no cottage 4, cottages go from 1 to 3"
+    ));
+    assert!(game.gateway.channels.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn full_day_with_execution() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 5, 5).await;
+
+    game.start_day().await.unwrap();
+    // Player 5 died last night and still has their ghost vote
+    game.mark_dead(player(5)).await.unwrap();
+
+    let events = game
+        .start_vote(TOWN_SQUARE, nomination(1, 2))
+        .await
+        .unwrap();
+    assert_eq!(
+        events,
+        vec![GameEvent::VoteStarted {
+            nominator: player(1),
+            nominee: player(2),
+            kind: VoteKind::Execution
+        }]
+    );
+
+    let message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+    let message = game.gateway.message(message_id).unwrap();
+    assert_eq!(message.channel_id, TOWN_SQUARE);
+    assert_eq!(message.buttons, VOTE_BUTTONS);
+    assert!(message.content.contains("<@5> (Dead)"));
+    assert!(message.content.contains("Clockhand on <@3>"));
+
+    // Hands go up through the buttons
+    for voter in [3, 4, 5] {
+        game.set_hand(player(voter), true).await.unwrap();
+    }
+    assert!(
+        game.gateway
+            .message(message_id)
+            .unwrap()
+            .content
+            .contains("<@4> 🙋")
+    );
+
+    let mut ghost_votes = vec![];
+    for yes in [true, true, true, false, false] {
+        for event in game.cast_vote(yes).await.unwrap() {
+            if let GameEvent::GhostVoteUsed { voter } = event {
+                ghost_votes.push(voter);
+            }
+        }
+    }
+    assert_eq!(ghost_votes, [player(5)]);
+    assert!(game.gateway.has_role(player(5), DEAD));
+    assert!(!game.gateway.has_role(player(5), GHOST_VOTE));
+
+    let message = game.gateway.message(message_id).unwrap();
+    assert!(message.content.contains("<@5> (Dead Vote Used) ✅"));
+    // Edits leave the hand buttons in place
+    assert_eq!(message.buttons, VOTE_BUTTONS);
+
+    let events = game.end_day().await.unwrap();
+    assert_eq!(
+        events,
+        vec![
+            GameEvent::Executed {
+                player: player(2),
+                votes: 3
+            },
+            GameEvent::NightFell
+        ]
+    );
+    assert!(game.gateway.has_role(player(2), DEAD));
+    assert!(game.gateway.has_role(player(2), GHOST_VOTE));
+    assert_eq!(
+        game.alive_players().await.unwrap().len(),
+        3,
+        "players 2 and 5 are dead"
+    );
+}
+
+#[tokio::test]
+async fn hands_are_locked_once_the_clockhand_passes() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+
+    game.start_vote(TOWN_SQUARE, nomination(1, 3))
+        .await
+        .unwrap();
+    let message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+
+    game.cast_vote(true).await.unwrap();
+    let before = game.gateway.message(message_id).unwrap();
+
+    let result = game.set_hand(player(1), false).await;
+    assert!(matches!(
+        result,
+        Err(Error::Game(GameError::AlreadyVoted(voter))) if voter == player(1)
+    ));
+    let after = game.gateway.message(message_id).unwrap();
+    assert_eq!(before.embed, after.embed);
+}
+
+#[tokio::test]
+async fn dead_players_without_a_ghost_vote_cant_vote_yes() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 4, 4).await;
+    game.mark_dead(player(3)).await.unwrap();
+    game.gateway
+        .remove_roles(player(3), &[GHOST_VOTE])
+        .await
+        .unwrap();
+
+    game.start_vote(TOWN_SQUARE, nomination(1, 2))
+        .await
+        .unwrap();
+    let result = game.cast_vote(true).await;
+    assert!(matches!(
+        result,
+        Err(Error::Game(GameError::NoGhostVote(voter))) if voter == player(3)
+    ));
+    // The clock hand stays put until they vote no
+    game.cast_vote(false).await.unwrap();
+    for _ in 0..3 {
+        game.cast_vote(true).await.unwrap();
+    }
+
+    let state = state.read().await;
+    let vote = state.current_vote.as_ref().unwrap();
+    assert_eq!(state.vote_modifiers().tally(vote).votes, 3);
+    let embed = game
+        .gateway
+        .message(vote.message_id)
+        .unwrap()
+        .embed
+        .unwrap();
+    assert_eq!(embed["fields"][2]["value"], "3");
+}
+
+#[tokio::test]
+async fn secret_ballots_keep_ghost_votes_until_the_vote_is_over() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 4, 4).await;
+    game.mark_dead(player(3)).await.unwrap();
+    game.mark_dead(player(4)).await.unwrap();
+
+    let secret = |nominator, nominee| Nomination {
+        secret: true,
+        ..nomination(nominator, nominee)
+    };
+    game.start_vote(TOWN_SQUARE, secret(1, 2)).await.unwrap();
+    game.cast_vote(true).await.unwrap();
+    assert!(game.gateway.has_role(player(3), GHOST_VOTE));
+    for _ in 0..3 {
+        game.cast_vote(true).await.unwrap();
+    }
+    assert!(!game.gateway.has_role(player(3), GHOST_VOTE));
+    assert!(!game.gateway.has_role(player(4), GHOST_VOTE));
+
+    // Revealing the ballot early gives the roles away too
+    game.gateway
+        .add_roles(player(3), &[GHOST_VOTE])
+        .await
+        .unwrap();
+    game.start_vote(TOWN_SQUARE, secret(4, 2)).await.unwrap();
+    game.cast_vote(true).await.unwrap();
+    assert!(game.gateway.has_role(player(3), GHOST_VOTE));
+    game.reveal_ghost_votes().await.unwrap();
+    assert!(!game.gateway.has_role(player(3), GHOST_VOTE));
+}
+
+#[tokio::test]
+async fn revealed_ballots_announce_the_count() {
+    let config = config(MessageStyle::PlainText);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    let secret = Nomination {
+        secret: true,
+        ..nomination(1, 2)
+    };
+    game.start_vote(TOWN_SQUARE, secret).await.unwrap();
+    game.cast_vote(true).await.unwrap();
+    game.cast_vote(true).await.unwrap();
+    let announcement = || {
+        let messages = game.gateway.messages.lock().unwrap();
+        let (_, message) = messages.last().unwrap();
+        assert_eq!(message.channel_id, TOWN_SQUARE);
+        message.content.clone()
+    };
+
+    game.reveal_votes(false).await.unwrap();
+    assert_eq!(announcement(), "The vote stands at **2** votes so far");
+    assert!(state.read().await.current_vote.as_ref().unwrap().secret);
+
+    game.cast_vote(false).await.unwrap();
+    game.reveal_votes(true).await.unwrap();
+    assert_eq!(announcement(), "The vote ended with **2** votes");
+    assert!(!state.read().await.current_vote.as_ref().unwrap().secret);
+}
+
+#[tokio::test]
+async fn votes_skip_empty_cottages() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    // Cottages 4 and 5 stay empty
+    let game = seated_game(&config, &state, 5, 3).await;
+
+    game.start_vote(TOWN_SQUARE, nomination(1, 3))
+        .await
+        .unwrap();
+
+    let mut voters = vec![];
+    let mut complete = false;
+    for _ in 0..3 {
+        for event in game.cast_vote(true).await.unwrap() {
+            match event {
+                GameEvent::Voted { voter, .. } => voters.push(voter),
+                GameEvent::VoteComplete => complete = true,
+                _ => (),
+            }
+        }
+    }
+    assert_eq!(voters, [player(1), player(2), player(3)]);
+    assert!(complete);
+
+    let state = state.read().await;
+    let vote = state.current_vote.as_ref().unwrap();
+    let embed = game
+        .gateway
+        .message(vote.message_id)
+        .unwrap()
+        .embed
+        .unwrap();
+    assert_eq!(embed["fields"][2]["value"], "3");
+    assert_eq!(embed["fields"][3]["value"], "2");
+}
+
+#[tokio::test]
+async fn the_vote_message_counts_votes_like_the_result() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 4, 4).await;
+    state
+        .write()
+        .await
+        .reminders
+        .insert(player(2), vec![ReminderToken::BureaucratThreeVotes]);
+
+    game.start_vote(TOWN_SQUARE, nomination(3, 4))
+        .await
+        .unwrap();
+    for yes in [false, true, false, false] {
+        game.cast_vote(yes).await.unwrap();
+    }
+
+    // A single Yes, counted three times, puts player 4 on the block
+    let message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+    let embed = game.gateway.message(message_id).unwrap().embed.unwrap();
+    assert_eq!(embed["fields"][2]["value"], "3");
+    assert_eq!(embed["fields"][3]["value"], "2");
+    assert_eq!(embed["color"], Colour::RED.0);
+    let events = game.end_day().await.unwrap();
+    assert_eq!(
+        events[0],
+        GameEvent::Executed {
+            player: player(4),
+            votes: 3
+        }
+    );
+}
+
+#[tokio::test]
+async fn tied_votes_execute_nobody() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 4, 4).await;
+    game.start_day().await.unwrap();
+
+    for nominee in [1, 3] {
+        game.start_vote(TOWN_SQUARE, nomination(2, nominee))
+            .await
+            .unwrap();
+        for yes in [true, true, false, false] {
+            game.cast_vote(yes).await.unwrap();
+        }
+    }
+
+    let events = game.end_day().await.unwrap();
+    assert_eq!(
+        events[0],
+        GameEvent::ExecutionTied {
+            nominees: vec![player(1), player(3)],
+            votes: 2
+        }
+    );
+    for n in 1..=4 {
+        assert!(
+            game.gateway
+                .member_roles(player(n))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
+
+#[tokio::test]
+async fn a_day_only_ends_once() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 4, 4).await;
+    game.start_day().await.unwrap();
+    game.start_vote(TOWN_SQUARE, nomination(2, 1))
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        game.cast_vote(true).await.unwrap();
+    }
+
+    // The day ends before player 1 votes, the three votes so far still execute them
+    let events = game.end_day().await.unwrap();
+    assert_eq!(
+        events,
+        [
+            GameEvent::VoteCutShort { nominee: player(1) },
+            GameEvent::Executed {
+                player: player(1),
+                votes: 3
+            },
+            GameEvent::NightFell
+        ]
+    );
+
+    let result = game.end_day().await;
+    assert!(matches!(result, Err(Error::Game(GameError::NotDay))));
+    state.write().await.phase = Phase::GameOver;
+    let result = game.end_day().await;
+    assert!(matches!(result, Err(Error::Game(GameError::NotDay))));
+    assert!(game.gateway.has_role(player(1), DEAD));
+    assert_eq!(state.read().await.phase, Phase::GameOver);
+}
+
+#[tokio::test]
+async fn only_travellers_can_be_exiled() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 2).await;
+    game.seat_player(3, player(3), None, Some(true))
+        .await
+        .unwrap();
+
+    let exile = |nominee| Nomination {
+        kind: VoteKind::Exile,
+        ..nomination(1, nominee)
+    };
+    let result = game.start_vote(TOWN_SQUARE, exile(2)).await;
+    assert!(matches!(
+        result,
+        Err(Error::Game(GameError::NotATraveller(_)))
+    ));
+    assert!(game.gateway.messages.lock().unwrap().is_empty());
+
+    game.start_vote(TOWN_SQUARE, exile(3)).await.unwrap();
+    assert_eq!(
+        state.read().await.current_vote.as_ref().unwrap().kind,
+        VoteKind::Exile
+    );
+
+    // A Traveller character can be exiled without being seated as one
+    state.write().await.characters.insert(
+        player(2),
+        Character {
+            name: "Beggar".to_owned(),
+            character_type: CharacterType::Traveller,
+        },
+    );
+    game.start_vote(TOWN_SQUARE, exile(2)).await.unwrap();
+    assert_eq!(
+        state.read().await.current_vote.as_ref().unwrap().nominee,
+        player(2)
+    );
+}
+
+#[tokio::test]
+async fn vote_messages_show_the_town_square() {
+    let mut config = config(MessageStyle::Embed);
+    config.town_square.enabled = true;
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+
+    game.start_vote(TOWN_SQUARE, nomination(1, 2))
+        .await
+        .unwrap();
+
+    let message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+    let message = game.gateway.message(message_id).unwrap();
+    assert!(message.has_attachment);
+    assert_eq!(
+        message.embed.unwrap()["image"]["url"],
+        "attachment://town_square.png"
+    );
+    // Names are remembered for the next render
+    let names = state.read().await.display_names.clone();
+    assert_eq!(names.len(), 3);
+    assert_eq!(names[&player(1)], "Player 1");
+}
+
+#[tokio::test]
+async fn nominators_and_nominees_make_their_own_case() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    game.start_vote(TOWN_SQUARE, nomination(1, 2))
+        .await
+        .unwrap();
+    let message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+
+    game.set_statement(
+        Statement::Accusation,
+        player(1),
+        false,
+        "Bad vibes".to_owned(),
+    )
+    .await
+    .unwrap();
+    game.set_statement(
+        Statement::Defense,
+        player(2),
+        false,
+        "I'm the Saint".to_owned(),
+    )
+    .await
+    .unwrap();
+    let embed = game.gateway.message(message_id).unwrap().embed.unwrap();
+    assert_eq!(embed["fields"][0]["value"], "Bad vibes");
+    assert_eq!(embed["fields"][1]["value"], "I'm the Saint");
+
+    // Nobody else gets to speak for them, except the storytellers
+    for (statement, user) in [(Statement::Accusation, 2), (Statement::Defense, 3)] {
+        let result = game.statement(statement, player(user), false).await;
+        assert!(matches!(result, Err(Error::Permission(_))));
+        let result = game
+            .set_statement(statement, player(user), false, "Lies".to_owned())
+            .await;
+        assert!(matches!(result, Err(Error::Permission(_))));
+    }
+    game.set_statement(Statement::Defense, player(3), true, "Overruled".to_owned())
+        .await
+        .unwrap();
+    assert_eq!(
+        game.statement(Statement::Defense, player(2), false)
+            .await
+            .unwrap(),
+        "Overruled"
+    );
+
+    let result = game
+        .set_statement(
+            Statement::Accusation,
+            player(1),
+            false,
+            "a".repeat(MAX_STATEMENT_LENGTH + 1),
+        )
+        .await;
+    assert!(matches!(result, Err(Error::Validation(_))));
+    let embed = game.gateway.message(message_id).unwrap().embed.unwrap();
+    assert_eq!(embed["fields"][0]["value"], "Bad vibes");
+}
+
+#[tokio::test]
+async fn hands_lock_at_the_deadline() {
+    let mut config = config(MessageStyle::PlainText);
+    config.deadlines.statement_minutes = Some(60);
+    config.deadlines.hand_minutes = Some(120);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 3, 3).await;
+    game.start_vote(TOWN_SQUARE, nomination(1, 2))
+        .await
+        .unwrap();
+    let message_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+
+    let content = game.gateway.message(message_id).unwrap().content;
+    assert!(content.contains("⏰ Accusation and defense due <t:"));
+    assert!(content.contains("⏰ Hands lock <t:"));
+    assert!(!content.contains("Vote due"));
+
+    let now = SystemTime::now();
+    let events = game.check_deadlines(now).await.unwrap();
+    assert!(events.is_empty());
+    game.set_hand(player(3), true).await.unwrap();
+
+    let later = now + Duration::from_secs(3 * 60 * 60);
+    let events = game.check_deadlines(later).await.unwrap();
+    assert_eq!(
+        events,
+        [
+            GameEvent::DeadlinePassed {
+                deadline: Deadline::Statements,
+                nominee: player(2)
+            },
+            GameEvent::DeadlinePassed {
+                deadline: Deadline::Hands,
+                nominee: player(2)
+            }
+        ]
+    );
+    let messages = game.gateway.messages.lock().unwrap().clone();
+    let (_, ping) = messages.last().unwrap();
+    assert_eq!(ping.channel_id, TOWN_SQUARE);
+    assert_eq!(
+        ping.content,
+        format!("<@&{STORYTELLER}> Hands are now locked for the nomination of <@2>")
+    );
+    assert!(
+        game.gateway
+            .message(message_id)
+            .unwrap()
+            .content
+            .contains("⏰ Hands locked <t:")
+    );
+
+    let result = game.set_hand(player(1), true).await;
+    assert!(matches!(result, Err(Error::Game(GameError::HandsLocked))));
+    // Statements are final too, unless a storyteller steps in
+    let result = game
+        .set_statement(Statement::Defense, player(2), false, "Wait".to_owned())
+        .await;
+    assert!(matches!(result, Err(Error::Game(GameError::StatementsDue))));
+    game.set_statement(Statement::Defense, player(3), true, "Too late".to_owned())
+        .await
+        .unwrap();
+    // Each deadline is only announced once
+    assert!(game.check_deadlines(later).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn players_are_pinged_when_the_clockhand_reaches_them() {
+    let config = config(MessageStyle::Embed);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 4, 4).await;
+    state
+        .write()
+        .await
+        .clock_hand_ping_opt_outs
+        .insert(player(3));
+    game.start_vote(TOWN_SQUARE, nomination(1, 1))
+        .await
+        .unwrap();
+    let vote_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+    let cottage = |n| {
+        let state = state.try_read().unwrap();
+        state.players.values().find(|i| i.0 == player(n)).unwrap().1
+    };
+    let pings = |n| {
+        game.gateway
+            .messages
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, i)| i.channel_id == cottage(n))
+            .map(|(_, i)| i.clone())
+            .collect::<Vec<_>>()
+    };
+
+    // Player 2 goes first and is pinged as the vote opens, then the clock hand moves on to
+    // player 3
+    assert_eq!(pings(2).len(), 1);
+    game.cast_vote(true).await.unwrap();
+    assert!(pings(3).is_empty(), "player 3 opted out");
+
+    let result = game.cast_own_vote(vote_id, player(4), true).await;
+    assert!(matches!(
+        result,
+        Err(Error::Game(GameError::NotYourTurn(voter))) if voter == player(4)
+    ));
+    game.cast_own_vote(vote_id, player(3), false).await.unwrap();
+
+    let ping = &pings(4)[0];
+    assert_eq!(
+        ping.content,
+        "<@4> the clock hand is on you for the nomination of <@1>, how do you vote?"
+    );
+    assert_eq!(
+        ping.buttons,
+        [
+            format!("clock_hand_yes_{vote_id}"),
+            format!("clock_hand_no_{vote_id}")
+        ]
+    );
+    game.cast_own_vote(vote_id, player(4), true).await.unwrap();
+    assert_eq!(pings(1).len(), 1);
+
+    // Nobody is left to ping once everyone has voted
+    game.cast_own_vote(vote_id, player(1), true).await.unwrap();
+    assert_eq!(pings(2).len(), 1);
+    assert_eq!(pings(1).len(), 1);
+
+    // A ping left over from the last nomination doesn't vote in the next one
+    game.start_vote(TOWN_SQUARE, nomination(1, 3))
+        .await
+        .unwrap();
+    assert_eq!(pings(4).len(), 2);
+    let result = game.cast_own_vote(vote_id, player(4), true).await;
+    assert!(matches!(result, Err(Error::Game(GameError::VoteClosed))));
+    let next_vote = state.read().await.current_vote.as_ref().unwrap().message_id;
+    game.cast_own_vote(next_vote, player(4), true)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn players_voting_for_themselves_follow_the_same_rules() {
+    let mut config = config(MessageStyle::PlainText);
+    config.deadlines.hand_minutes = Some(60);
+    config.deadlines.vote_minutes = Some(120);
+    let state = RwLock::new(State::default());
+    let game = seated_game(&config, &state, 4, 4).await;
+    game.mark_dead(player(2)).await.unwrap();
+    game.gateway
+        .remove_roles(player(2), &[GHOST_VOTE])
+        .await
+        .unwrap();
+    game.start_vote(TOWN_SQUARE, nomination(1, 1))
+        .await
+        .unwrap();
+    let vote_id = state.read().await.current_vote.as_ref().unwrap().message_id;
+    game.set_hand(player(3), true).await.unwrap();
+
+    let result = game.cast_own_vote(vote_id, player(2), true).await;
+    assert!(matches!(
+        result,
+        Err(Error::Game(GameError::NoGhostVote(voter))) if voter == player(2)
+    ));
+    game.cast_own_vote(vote_id, player(2), false).await.unwrap();
+
+    // Once hands are locked the vote has to match the hand
+    let now = SystemTime::now();
+    game.check_deadlines(now + Duration::from_secs(90 * 60))
+        .await
+        .unwrap();
+    let result = game.cast_own_vote(vote_id, player(3), false).await;
+    assert!(matches!(result, Err(Error::Game(GameError::HandsLocked))));
+    game.cast_own_vote(vote_id, player(3), true).await.unwrap();
+    let result = game.cast_own_vote(vote_id, player(4), true).await;
+    assert!(matches!(result, Err(Error::Game(GameError::HandsLocked))));
+
+    // After the vote deadline only the storytellers lock in votes
+    game.check_deadlines(now + Duration::from_secs(150 * 60))
+        .await
+        .unwrap();
+    let result = game.cast_own_vote(vote_id, player(4), false).await;
+    assert!(matches!(result, Err(Error::Game(GameError::VoteDue))));
+    game.cast_vote(false).await.unwrap();
+}
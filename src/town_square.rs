@@ -0,0 +1,277 @@
+//! Draws the current vote as a circular town square, the way it looks on a physical table
+
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    fmt::Write,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+
+use poise::serenity_prelude::{CreateAttachment, UserId};
+use resvg::{tiny_skia, usvg};
+use serde::Deserialize;
+
+use crate::state::{CottageNumber, DeadState, PlayerMap, Vote, VoteState};
+
+pub const TOWN_SQUARE_FILENAME: &str = "town_square.png";
+
+const SIZE: f32 = 800.0;
+const CENTER: f32 = SIZE / 2.0;
+const TABLE_RADIUS: f32 = 290.0;
+/// Generic families only resolve to fonts fontdb guesses at, so name common ones explicitly
+const FONT_FAMILIES: &str = "DejaVu Sans, Noto Sans, Liberation Sans, Arial, sans-serif";
+
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct TownSquareConfig {
+    /// Attach a picture of the table to every vote message. Off by default, drawing the names
+    /// needs fonts on the host.
+    pub enabled: bool,
+    /// Extra fonts to use for player names, for servers without system fonts
+    pub font_dir: Option<PathBuf>,
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Position of a cottage's token, cottage 1 sits at the top and the rest follow clockwise
+fn seat_position(cottage: u32, number_of_players: u32, radius: f32) -> (f32, f32) {
+    let angle = -PI / 2.0 + 2.0 * PI * (cottage - 1) as f32 / number_of_players as f32;
+    (CENTER + radius * angle.cos(), CENTER + radius * angle.sin())
+}
+
+/// The town square as an SVG document, names fall back to user IDs when missing
+pub fn town_square_svg(
+    players: &PlayerMap,
+    vote: &Vote,
+    number_of_players: u32,
+    names: &HashMap<UserId, String>,
+) -> String {
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{SIZE}\" height=\"{SIZE}\" viewBox=\"0 0 {SIZE} {SIZE}\" font-family=\"{FONT_FAMILIES}\">\n\
+         <rect width=\"{SIZE}\" height=\"{SIZE}\" fill=\"#2b2d31\"/>\n\
+         <circle cx=\"{CENTER}\" cy=\"{CENTER}\" r=\"{TABLE_RADIUS}\" fill=\"none\" stroke=\"#4e5058\" stroke-width=\"2\"/>\n"
+    );
+    if number_of_players == 0 {
+        out.push_str("</svg>\n");
+        return out;
+    }
+
+    // Tokens shrink on big tables so neighbours don't overlap
+    let token_radius = (PI * TABLE_RADIUS / number_of_players as f32 * 0.7).min(44.0);
+
+    // The clockhand goes underneath the tokens
+    let (x, y) = seat_position(
+        vote.clock_hand.0.get(),
+        number_of_players,
+        TABLE_RADIUS - token_radius - 12.0,
+    );
+    let angle = (y - CENTER).atan2(x - CENTER);
+    let wing = |offset: f32| {
+        (
+            x - 26.0 * (angle + offset).cos(),
+            y - 26.0 * (angle + offset).sin(),
+        )
+    };
+    let (left, right) = (wing(0.5), wing(-0.5));
+    let _ = writeln!(
+        out,
+        "<line x1=\"{CENTER}\" y1=\"{CENTER}\" x2=\"{x:.1}\" y2=\"{y:.1}\" stroke=\"#f2f3f5\" stroke-width=\"8\" stroke-linecap=\"round\"/>\n\
+         <polygon points=\"{x:.1},{y:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"#f2f3f5\"/>\n\
+         <circle cx=\"{CENTER}\" cy=\"{CENTER}\" r=\"12\" fill=\"#f2f3f5\"/>",
+        left.0, left.1, right.0, right.1
+    );
+
+    for cottage in 1..=number_of_players {
+        let (x, y) = seat_position(cottage, number_of_players, TABLE_RADIUS);
+        let r = token_radius;
+
+        let Some((player, _)) = CottageNumber::new(cottage).and_then(|i| players.get(&i)) else {
+            let _ = writeln!(
+                out,
+                "<circle cx=\"{x:.1}\" cy=\"{y:.1}\" r=\"{r:.1}\" fill=\"none\" stroke=\"#80848e\" stroke-width=\"3\" stroke-dasharray=\"8 6\"/>\n\
+                 <text x=\"{x:.1}\" y=\"{:.1}\" font-size=\"16\" fill=\"#80848e\" text-anchor=\"middle\">Empty</text>",
+                y + r + 20.0
+            );
+            continue;
+        };
+
+        let dead_state = vote.dead_state.get(player).unwrap_or(&DeadState::Alive);
+        let dead = !matches!(dead_state, DeadState::Alive);
+        let _ = writeln!(
+            out,
+            "<circle cx=\"{x:.1}\" cy=\"{y:.1}\" r=\"{r:.1}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            if dead { "#6d6f78" } else { "#f3e9d2" },
+            if *player == vote.nominee {
+                "#da373c"
+            } else {
+                "#5a4632"
+            },
+            if *player == vote.nominee { 6 } else { 3 },
+        );
+
+        if dead {
+            // A shroud draped over the top of the token
+            let _ = writeln!(
+                out,
+                "<polygon points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1} {x:.1},{:.1} {:.1},{:.1}\" fill=\"#111214\" fill-opacity=\"0.85\"/>",
+                x - r * 0.6,
+                y - r * 0.85,
+                x + r * 0.6,
+                y - r * 0.85,
+                x + r * 0.6,
+                y + r * 0.15,
+                y + r * 0.55,
+                x - r * 0.6,
+                y + r * 0.15,
+            );
+        }
+        if matches!(dead_state, DeadState::DeadVoteAvailable) && !vote.secret {
+            let _ = writeln!(
+                out,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"#ffffff\" stroke=\"#111214\" stroke-width=\"2\"/>",
+                x + r * 0.72,
+                y + r * 0.72,
+                r * 0.26
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "<text x=\"{x:.1}\" y=\"{:.1}\" font-size=\"18\" fill=\"#f2f3f5\" text-anchor=\"middle\">{}</text>",
+            y + r + 22.0,
+            escape_xml(
+                &names
+                    .get(player)
+                    .cloned()
+                    .unwrap_or_else(|| player.to_string())
+            )
+        );
+
+        // Markers sit on the outside of the token, facing away from the table
+        let (mx, my) = seat_position(cottage, number_of_players, TABLE_RADIUS + r * 0.85);
+        let m = (r * 0.38).max(10.0);
+        let marker = match (vote.vote_state.get(player), vote.secret) {
+            (Some(VoteState::Yes | VoteState::No), true) => format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"3\" fill=\"#949ba4\" stroke=\"#111214\" stroke-width=\"2\"/>",
+                mx - m * 0.8,
+                my - m * 0.8,
+                m * 1.6,
+                m * 1.6
+            ),
+            (Some(VoteState::Yes), false) => format!(
+                "<circle cx=\"{mx:.1}\" cy=\"{my:.1}\" r=\"{m:.1}\" fill=\"#248046\"/>\n\
+                 <polyline points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"none\" stroke=\"#ffffff\" stroke-width=\"4\" stroke-linecap=\"round\"/>",
+                mx - m * 0.5,
+                my,
+                mx - m * 0.1,
+                my + m * 0.4,
+                mx + m * 0.5,
+                my - m * 0.4
+            ),
+            (Some(VoteState::No), false) => format!(
+                "<circle cx=\"{mx:.1}\" cy=\"{my:.1}\" r=\"{m:.1}\" fill=\"#da373c\"/>\n\
+                 <path d=\"M{:.1},{:.1} L{:.1},{:.1} M{:.1},{:.1} L{:.1},{:.1}\" stroke=\"#ffffff\" stroke-width=\"4\" stroke-linecap=\"round\"/>",
+                mx - m * 0.4,
+                my - m * 0.4,
+                mx + m * 0.4,
+                my + m * 0.4,
+                mx + m * 0.4,
+                my - m * 0.4,
+                mx - m * 0.4,
+                my + m * 0.4
+            ),
+            // A palm with three raised fingers
+            (Some(VoteState::HandRaised), false) => format!(
+                "<g fill=\"#f0b232\"><rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"4\"/>\
+                 <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"2\"/>\
+                 <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"2\"/>\
+                 <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"2\"/></g>",
+                mx - m * 0.6,
+                my - m * 0.1,
+                m * 1.2,
+                m * 0.9,
+                mx - m * 0.6,
+                my - m,
+                m * 0.32,
+                m,
+                mx - m * 0.16,
+                my - m * 1.15,
+                m * 0.32,
+                m * 1.15,
+                mx + m * 0.28,
+                my - m,
+                m * 0.32,
+                m
+            ),
+            _ => String::new(),
+        };
+        if !marker.is_empty() {
+            out.push_str(&marker);
+            out.push('\n');
+        }
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+fn fonts(config: &TownSquareConfig) -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut fonts = usvg::fontdb::Database::new();
+            fonts.load_system_fonts();
+            if let Some(font_dir) = &config.font_dir {
+                fonts.load_fonts_dir(font_dir);
+            }
+            Arc::new(fonts)
+        })
+        .clone()
+}
+
+pub fn render_png(svg: &str, config: &TownSquareConfig) -> Result<Vec<u8>, String> {
+    let options = usvg::Options {
+        fontdb: fonts(config),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| e.to_string())?;
+
+    let size = tree.size().to_int_size();
+    let mut pixmap =
+        tiny_skia::Pixmap::new(size.width(), size.height()).ok_or("The town square has no size")?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|e| e.to_string())
+}
+
+/// Renders the town square for a vote, or `None` if it's disabled or failed to render
+pub async fn town_square_attachment(
+    config: &TownSquareConfig,
+    players: &PlayerMap,
+    vote: &Vote,
+    number_of_players: u32,
+    names: &HashMap<UserId, String>,
+) -> Option<CreateAttachment> {
+    if !config.enabled {
+        return None;
+    }
+
+    let svg = town_square_svg(players, vote, number_of_players, names);
+    let config = config.clone();
+    // Rasterizing takes long enough to stall the other tasks on this thread
+    let png = tokio::task::spawn_blocking(move || render_png(&svg, &config))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+    match png {
+        Ok(png) => Some(CreateAttachment::bytes(png, TOWN_SQUARE_FILENAME)),
+        Err(e) => {
+            println!("Failed to render the town square: {e}");
+            None
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! Turns the raw `message_log.jsonl` into a readable transcript of a single game
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use poise::serenity_prelude::{ChannelId, MessageId};
+use serde::{Deserialize, Serialize};
+
+use crate::message_log::{LogEvent, LogRecord};
+
+/// When each day of a game started and when the game ended
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct GameTimeline {
+    pub days: Vec<SystemTime>,
+    pub ended: Option<SystemTime>,
+}
+
+impl GameTimeline {
+    /// The day a message belongs to, counting from 1, or `None` if it was sent outside the game
+    fn day_of(&self, time: SystemTime) -> Option<usize> {
+        if self.ended.is_some_and(|ended| time > ended) {
+            return None;
+        }
+        self.days
+            .iter()
+            .rposition(|start| *start <= time)
+            .map(|i| i + 1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum TranscriptFormat {
+    Markdown,
+    Html,
+}
+
+impl TranscriptFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            TranscriptFormat::Markdown => "md",
+            TranscriptFormat::Html => "html",
+        }
+    }
+}
+
+pub struct TranscriptOptions {
+    pub timeline: GameTimeline,
+    /// Channels to include, every channel is included if this is empty
+    pub channels: HashSet<ChannelId>,
+    /// Cottages and whisper threads, only shown in the post-game reveal section. Every cottage
+    /// seated in the log is added to these.
+    pub cottage_channels: HashSet<ChannelId>,
+    pub reveal_cottages: bool,
+    pub channel_names: HashMap<ChannelId, String>,
+}
+
+struct TranscriptMessage {
+    time: SystemTime,
+    author: String,
+    channel_id: ChannelId,
+    content: String,
+    attachments: Vec<String>,
+    edited: bool,
+}
+
+/// Messages grouped by channel, in order of each channel's first message
+type ChannelLog = Vec<(ChannelId, Vec<TranscriptMessage>)>;
+
+pub struct Transcript {
+    days: Vec<(usize, ChannelLog)>,
+    reveals: ChannelLog,
+    channel_names: HashMap<ChannelId, String>,
+}
+
+/// Replays the log, applying every edit and delete so only the final text of each message is kept
+pub fn build_transcript(
+    records: impl IntoIterator<Item = LogRecord>,
+    mut options: TranscriptOptions,
+) -> Transcript {
+    let mut messages: HashMap<MessageId, TranscriptMessage> = HashMap::new();
+    let mut order = vec![];
+
+    for LogRecord { time, event, .. } in records {
+        match event {
+            LogEvent::NewMessage {
+                message_id,
+                content,
+                author,
+                channel_id,
+                thread,
+                attachments,
+            } => {
+                if let Some((thread_id, name)) = thread {
+                    options.channel_names.entry(thread_id).or_insert(name);
+                }
+                order.push(message_id);
+                messages.insert(
+                    message_id,
+                    TranscriptMessage {
+                        time,
+                        author: author.display_name().to_owned(),
+                        channel_id,
+                        content,
+                        attachments,
+                        edited: false,
+                    },
+                );
+            }
+            LogEvent::MessageEdit {
+                message_id,
+                content: Some(content),
+                ..
+            } => {
+                if let Some(message) = messages.get_mut(&message_id)
+                    && message.content != content
+                {
+                    message.content = content;
+                    message.edited = true;
+                }
+            }
+            LogEvent::MessageDelete { message_id, .. } => {
+                messages.remove(&message_id);
+            }
+            LogEvent::ThreadCreate {
+                thread_id, name, ..
+            } => {
+                options.channel_names.entry(thread_id).or_insert(name);
+            }
+            // Cottages that were vacated later are just as private
+            LogEvent::Seating { cottages } => {
+                options
+                    .cottage_channels
+                    .extend(cottages.into_iter().map(|(_, _, channel_id)| channel_id));
+            }
+            _ => (),
+        }
+    }
+
+    let mut days: Vec<(usize, ChannelLog)> = vec![];
+    let mut reveals: ChannelLog = vec![];
+    for message_id in order {
+        let Some(message) = messages.remove(&message_id) else {
+            continue;
+        };
+        let Some(day) = options.timeline.day_of(message.time) else {
+            continue;
+        };
+
+        let channels = if options.cottage_channels.contains(&message.channel_id) {
+            if !options.reveal_cottages {
+                continue;
+            }
+            &mut reveals
+        } else {
+            if !options.channels.is_empty() && !options.channels.contains(&message.channel_id) {
+                continue;
+            }
+            if days.last().is_none_or(|(i, _)| *i != day) {
+                days.push((day, vec![]));
+            }
+            &mut days.last_mut().unwrap().1
+        };
+
+        match channels.iter_mut().find(|(i, _)| *i == message.channel_id) {
+            Some((_, messages)) => messages.push(message),
+            None => channels.push((message.channel_id, vec![message])),
+        }
+    }
+
+    Transcript {
+        days,
+        reveals,
+        channel_names: options.channel_names,
+    }
+}
+
+/// `HH:MM` in UTC
+fn format_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|i| i.as_secs())
+        .unwrap_or_default();
+    format!("{:02}:{:02}", seconds / 3600 % 24, seconds / 60 % 60)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escapes anything Markdown would format in a message and keeps every line of it on its own
+/// line, blank lines would otherwise end the message early
+fn escape_markdown(text: &str) -> String {
+    let mut lines = vec![];
+    for line in text.lines().map(str::trim).filter(|i| !i.is_empty()) {
+        let mut escaped = String::new();
+        // Numbered lists start with digits and then a `.` or `)`
+        let digits = line.chars().take_while(char::is_ascii_digit).count();
+        for (i, c) in line.chars().enumerate() {
+            let special = matches!(c, '\\' | '*' | '_' | '`' | '~' | '[' | ']' | '<' | '>' | '|')
+                // Headings, quotes and lists only start at the beginning of a line
+                || (i == 0 && matches!(c, '#' | '-' | '+'))
+                || (i == digits && i > 0 && matches!(c, '.' | ')'));
+            if special {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        lines.push(escaped);
+    }
+    lines.join("  \n")
+}
+
+impl Transcript {
+    fn channel_name(&self, channel_id: ChannelId) -> String {
+        match self.channel_names.get(&channel_id) {
+            Some(name) => format!("#{name}"),
+            None => format!("#{channel_id}"),
+        }
+    }
+
+    fn sections(&self) -> impl Iterator<Item = (String, &ChannelLog)> {
+        self.days
+            .iter()
+            .map(|(day, channels)| (format!("Day {day}"), channels))
+            .chain(
+                (!self.reveals.is_empty()).then(|| ("Post-Game Reveals".to_owned(), &self.reveals)),
+            )
+    }
+
+    pub fn render(&self, format: TranscriptFormat) -> String {
+        match format {
+            TranscriptFormat::Markdown => self.render_markdown(),
+            TranscriptFormat::Html => self.render_html(),
+        }
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = String::from("# Game Transcript\n");
+        for (title, channels) in self.sections() {
+            let _ = write!(out, "\n## {title}\n");
+            for (channel_id, messages) in channels {
+                let _ = write!(out, "\n### {}\n\n", self.channel_name(*channel_id));
+                for message in messages {
+                    let _ = writeln!(
+                        out,
+                        "**{}** {}: {}{}  ",
+                        format_time(message.time),
+                        escape_markdown(&message.author),
+                        escape_markdown(&message.content),
+                        if message.edited { " *(edited)*" } else { "" }
+                    );
+                    for attachment in &message.attachments {
+                        let _ = writeln!(out, "<{attachment}>  ");
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn render_html(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Game Transcript</title></head>\n<body>\n<h1>Game Transcript</h1>\n",
+        );
+        for (title, channels) in self.sections() {
+            let _ = writeln!(out, "<h2>{}</h2>", escape_html(&title));
+            for (channel_id, messages) in channels {
+                let _ = writeln!(
+                    out,
+                    "<h3>{}</h3>",
+                    escape_html(&self.channel_name(*channel_id))
+                );
+                for message in messages {
+                    let _ = write!(
+                        out,
+                        "<p><time>{}</time> <b>{}</b>: {}{}",
+                        format_time(message.time),
+                        escape_html(&message.author),
+                        escape_html(&message.content).replace('\n', "<br>"),
+                        if message.edited {
+                            " <i>(edited)</i>"
+                        } else {
+                            ""
+                        }
+                    );
+                    for attachment in &message.attachments {
+                        let url = escape_html(attachment);
+                        let _ = write!(out, "<br><a href=\"{url}\">{url}</a>");
+                    }
+                    out.push_str("</p>\n");
+                }
+            }
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}